@@ -0,0 +1,118 @@
+use super::*;
+use chrono::Utc;
+use futures::future::{FutureExt, LocalBoxFuture, Shared};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::thread::LocalKey;
+
+type Pending<V> = Shared<LocalBoxFuture<'static, Result<V, ApiError>>>;
+
+/// Responses keyed by `K`, each valid for `ttl` milliseconds after it was fetched.
+///
+/// Lookups for a key that is already being fetched share the in-flight request.
+pub struct Cache<K, V: Clone> {
+    ttl: i64,
+    entries: HashMap<K, (i64, V)>,
+    pending: HashMap<K, Pending<V>>,
+}
+
+impl<K: Eq + Hash + Clone, V: Clone> Cache<K, V> {
+    pub fn new(ttl_secs: i64) -> Self {
+        Self {
+            ttl: ttl_secs * 1000,
+            entries: HashMap::new(),
+            pending: HashMap::new(),
+        }
+    }
+
+    fn fresh(&self, key: &K, now: i64) -> Option<V> {
+        match self.entries.get(key) {
+            Some((fetched_at, value)) if now - fetched_at < self.ttl => Some(value.clone()),
+            _ => None,
+        }
+    }
+
+    /// Drops `key`, including a request still in flight for it.
+    pub fn invalidate(&mut self, key: &K) {
+        self.entries.remove(key);
+        self.pending.remove(key);
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.pending.clear();
+    }
+}
+
+thread_local! {
+    pub static POSTS: RefCell<Cache<i64, ResponseBlock<ViewPostResponse>>> =
+        RefCell::new(Cache::new(POST_CACHE_TTL_SECS));
+    pub static USERS: RefCell<Cache<i32, ResponseBlock<InfoResponse>>> =
+        RefCell::new(Cache::new(USER_CACHE_TTL_SECS));
+    pub static PAGES: RefCell<Cache<(i64, i64), ResponseBlock<PostsResponse>>> =
+        RefCell::new(Cache::new(POST_CACHE_TTL_SECS));
+}
+
+/// Returns the cached value for `key`, or runs `fetch` and caches its result if `keep` accepts it.
+pub async fn get_or_fetch<K, V>(
+    cache: &'static LocalKey<RefCell<Cache<K, V>>>,
+    key: K,
+    keep: fn(&V) -> bool,
+    fetch: LocalBoxFuture<'static, Result<V, ApiError>>,
+) -> Result<V, ApiError>
+where
+    K: Eq + Hash + Clone + 'static,
+    V: Clone + 'static,
+{
+    let now = Utc::now().timestamp_millis();
+    if let Some(value) = cache.with(|c| c.borrow().fresh(&key, now)) {
+        return Ok(value);
+    }
+    let pending = cache.with(|c| c.borrow().pending.get(&key).cloned());
+    let request = match pending {
+        Some(request) => request,
+        None => {
+            let request = fetch.shared();
+            cache.with(|c| c.borrow_mut().pending.insert(key.clone(), request.clone()));
+            request
+        }
+    };
+    let result = request.clone().await;
+    cache.with(|c| {
+        let mut c = c.borrow_mut();
+        // Whoever finishes first stores the result. An invalidation in the meantime wins, even
+        // once another request for the key is in flight, which is then left to store its own.
+        let current = c
+            .pending
+            .get(&key)
+            .map_or(false, |pending| Shared::ptr_eq(pending, &request));
+        if current {
+            c.pending.remove(&key);
+            if let Ok(value) = &result {
+                if keep(value) {
+                    // Fresh from when the response came, not from when it was asked for.
+                    let fetched_at = Utc::now().timestamp_millis();
+                    c.entries.insert(key, (fetched_at, value.clone()));
+                }
+            }
+        }
+    });
+    result
+}
+
+pub fn invalidate_post(id: i64) {
+    POSTS.with(|c| c.borrow_mut().invalidate(&id));
+    invalidate_lists();
+}
+
+pub fn invalidate_lists() {
+    PAGES.with(|c| c.borrow_mut().clear());
+}
+
+/// Forgets everything, e.g. after the logged-in user changes.
+pub fn clear() {
+    POSTS.with(|c| c.borrow_mut().clear());
+    USERS.with(|c| c.borrow_mut().clear());
+    invalidate_lists();
+}
@@ -0,0 +1,352 @@
+use super::cache;
+use super::*;
+use crate::config;
+use crate::services::token;
+use futures::future::{FutureExt, LocalBoxFuture, Shared};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use std::cell::RefCell;
+use yew::utils::origin;
+
+type RefreshFuture = Shared<LocalBoxFuture<'static, Result<String, ApiError>>>;
+
+thread_local! {
+    static HTTP_CLIENT: reqwest::Client = reqwest::Client::new();
+    static REFRESH: RefCell<Option<RefreshFuture>> = RefCell::new(None);
+}
+
+fn pending_refresh() -> Option<RefreshFuture> {
+    REFRESH.with(|pending| pending.borrow().clone())
+}
+
+/// `ResponseBlock`s from the blog service report a rejected token in `body.error`.
+fn is_auth_error(value: &Value) -> bool {
+    value.pointer("/body/error").and_then(Value::as_str) == Some("AuthError")
+}
+
+/// Client for the blog backend.
+///
+/// Every endpoint goes through `get`/`post`, which build the URL from `base_url`
+/// and decode the body into a `ResponseBlock<T>`.
+#[derive(Clone)]
+pub struct ApiClient {
+    base_url: String,
+    client: reqwest::Client,
+    token: Option<String>,
+}
+
+impl ApiClient {
+    /// Client for the current origin, authenticated with the stored token (if any).
+    pub fn new() -> Self {
+        let config = config::get();
+        let base_url = if config.api_base_url.is_empty() {
+            origin().unwrap_or_default()
+        } else {
+            config.api_base_url.clone()
+        };
+        Self::with_base_url(base_url).with_token(token::load_valid())
+    }
+
+    pub fn with_base_url<S: Into<String>>(base_url: S) -> Self {
+        Self {
+            base_url: base_url.into(),
+            client: HTTP_CLIENT.with(|client| client.clone()),
+            token: None,
+        }
+    }
+
+    pub fn with_token(mut self, token: Option<String>) -> Self {
+        self.token = token;
+        self
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_url.trim_end_matches('/'), path)
+    }
+
+    fn token(&self) -> String {
+        self.token.clone().unwrap_or_default()
+    }
+
+    fn require_token(&self) -> Result<String, ApiError> {
+        match &self.token {
+            Some(token) if !token.is_empty() => Ok(token.clone()),
+            _ => Err(ApiError::Unauthorized),
+        }
+    }
+
+    async fn send_value(&self, request: reqwest::RequestBuilder) -> Result<Value, ApiError> {
+        let res = request.send().await?;
+        match res.status().as_u16() {
+            401 => return Err(ApiError::Unauthorized),
+            code if !res.status().is_success() => return Err(ApiError::Status(code)),
+            _ => {}
+        }
+        let text = res.text().await?;
+        Ok(serde_json::from_str(&text)?)
+    }
+
+    async fn send<T: DeserializeOwned>(
+        &self,
+        request: reqwest::RequestBuilder,
+    ) -> Result<ResponseBlock<T>, ApiError> {
+        Ok(serde_json::from_value(self.send_value(request).await?)?)
+    }
+
+    async fn get<T: DeserializeOwned, Q: Serialize + ?Sized>(
+        &self,
+        path: &str,
+        query: &Q,
+    ) -> Result<ResponseBlock<T>, ApiError> {
+        self.send(self.client.get(&self.url(path)).query(query))
+            .await
+    }
+
+    async fn post<T: DeserializeOwned, B: Serialize + ?Sized>(
+        &self,
+        path: &str,
+        body: &B,
+    ) -> Result<ResponseBlock<T>, ApiError> {
+        self.send(self.client.post(&self.url(path)).json(body))
+            .await
+    }
+
+    /// Sends an authenticated request built by `build` from the current token.
+    ///
+    /// If the backend rejects the token (HTTP 401 or `BlogError::AuthError`), the token is
+    /// refreshed once and the request replayed with the new one, unless another request already
+    /// refreshed it. Requests started while a refresh is in flight wait for it instead of sending
+    /// the stale token. A rejected replay logs out. Unless the request `required` a login, it
+    /// is sent without a token when the refresh fails.
+    async fn send_authed<T, F>(
+        &self,
+        required: bool,
+        build: F,
+    ) -> Result<ResponseBlock<T>, ApiError>
+    where
+        T: DeserializeOwned,
+        F: Fn(String) -> reqwest::RequestBuilder,
+    {
+        let token = match pending_refresh() {
+            Some(refresh) if required => refresh.await?,
+            Some(refresh) => refresh.await.unwrap_or_default(),
+            None if required => self.require_token()?,
+            None => self.token(),
+        };
+        let first = self.send_value(build(token.clone())).await;
+        let rejected = match &first {
+            Ok(value) => is_auth_error(value),
+            Err(e) => e.is_unauthorized(),
+        };
+        if !rejected || token.is_empty() {
+            return Ok(serde_json::from_value(first?)?);
+        }
+        // A refresh that finished while this request was in flight has already replaced the
+        // token it was sent with, and refreshing that one again fails once it is rotated.
+        let token = match token::load() {
+            Some(current) if !current.is_empty() && current != token => current,
+            _ => match self.refresh(token).await {
+                Err(_) if !required => String::new(),
+                refreshed => refreshed?,
+            },
+        };
+        let replayed = match self.send_value(build(token)).await {
+            Err(e) if e.is_unauthorized() => None,
+            Err(e) => return Err(e),
+            Ok(value) if is_auth_error(&value) => None,
+            Ok(value) => Some(value),
+        };
+        match replayed {
+            Some(value) => Ok(serde_json::from_value(value)?),
+            None => {
+                token::clear();
+                Err(ApiError::Unauthorized)
+            }
+        }
+    }
+
+    /// Posts `body` wrapped in an `AsRequest` carrying the current token.
+    async fn post_as<T: DeserializeOwned, B: Serialize>(
+        &self,
+        path: &str,
+        body: B,
+    ) -> Result<ResponseBlock<T>, ApiError> {
+        let url = self.url(path);
+        self.send_authed(false, |token| {
+            self.client
+                .post(&url)
+                .json(&AsRequest { token, body: &body })
+        })
+        .await
+    }
+
+    /// Like `post_as`, but fails with `ApiError::Unauthorized` without a stored token.
+    async fn post_authed<T: DeserializeOwned, B: Serialize>(
+        &self,
+        path: &str,
+        body: B,
+    ) -> Result<ResponseBlock<T>, ApiError> {
+        let url = self.url(path);
+        self.send_authed(true, |token| {
+            self.client
+                .post(&url)
+                .json(&AsRequest { token, body: &body })
+        })
+        .await
+    }
+
+    /// Exchanges `stale` for a fresh token, sharing one in-flight refresh between callers.
+    async fn refresh(&self, stale: String) -> Result<String, ApiError> {
+        if let Some(refresh) = pending_refresh() {
+            return refresh.await;
+        }
+        let client = self.clone();
+        let refresh = async move {
+            let result = client.request_refresh(stale).await;
+            REFRESH.with(|pending| pending.borrow_mut().take());
+            match &result {
+                Ok(token) => token::replace(token),
+                Err(_) => token::clear(),
+            }
+            result
+        }
+        .boxed_local()
+        .shared();
+        REFRESH.with(|pending| *pending.borrow_mut() = Some(refresh.clone()));
+        refresh.await
+    }
+
+    async fn request_refresh(&self, token: String) -> Result<String, ApiError> {
+        let resp: ResponseBlock<LoginResponse> = self
+            .post("/api/account_service/refresh", &RefreshForm { token })
+            .await?;
+        match resp.body {
+            Some(LoginResponse {
+                result: AccountError::Nothing,
+                token: Some(token),
+            }) => Ok(token),
+            Some(LoginResponse { result, .. }) if result != AccountError::Nothing => {
+                Err(result.into())
+            }
+            _ => Err(ApiError::Unauthorized),
+        }
+    }
+
+    pub async fn login(&self, form: LoginForm) -> Result<ResponseBlock<LoginResponse>, ApiError> {
+        self.post("/api/account_service/login", &form).await
+    }
+
+    pub async fn register(
+        &self,
+        form: RegisterForm,
+    ) -> Result<ResponseBlock<RegisterResponse>, ApiError> {
+        self.post("/api/account_service/register", &form).await
+    }
+
+    pub async fn get_info(&self) -> Result<ResponseBlock<InfoResponse>, ApiError> {
+        let url = self.url("/api/account_service/info");
+        self.send_authed(true, |token| {
+            self.client.get(&url).query(&[("token", token)])
+        })
+        .await
+    }
+
+    pub async fn get_info_by_pk(&self, pk: i32) -> Result<ResponseBlock<InfoResponse>, ApiError> {
+        let client = self.clone();
+        let fetch = async move {
+            client
+                .get("/api/account_service/get_user", &[("pk", pk)])
+                .await
+        };
+        cache::get_or_fetch(&cache::USERS, pk, |block| block.status, fetch.boxed_local()).await
+    }
+
+    pub async fn get_post_counts(&self) -> Result<ResponseBlock<CountPostsResponse>, ApiError> {
+        self.get::<_, [(&str, i64)]>("/api/blog/count_posts", &[])
+            .await
+    }
+
+    pub async fn recent_posts(&self) -> Result<ResponseBlock<RecentPostsResponse>, ApiError> {
+        let count = config::get().recent_posts;
+        self.get("/api/blog/recent_posts", &[("count", count)])
+            .await
+    }
+
+    pub async fn posts(
+        &self,
+        start: i64,
+        count: i64,
+    ) -> Result<ResponseBlock<PostsResponse>, ApiError> {
+        let client = self.clone();
+        let fetch = async move {
+            client
+                .get("/api/blog/posts", &[("start", start), ("count", count)])
+                .await
+        };
+        let keep = |block: &ResponseBlock<PostsResponse>| {
+            block.status
+                && block
+                    .body
+                    .as_ref()
+                    .map_or(false, |b| b.error == BlogError::Nothing)
+        };
+        cache::get_or_fetch(&cache::PAGES, (start, count), keep, fetch.boxed_local()).await
+    }
+
+    pub async fn view_post(&self, id: i64) -> Result<ResponseBlock<ViewPostResponse>, ApiError> {
+        let client = self.clone();
+        let fetch = async move {
+            client
+                .post_as("/api/blog/view_post", ViewPostForm { id })
+                .await
+        };
+        let keep = |block: &ResponseBlock<ViewPostResponse>| {
+            block
+                .body
+                .as_ref()
+                .map_or(false, |b| b.error == BlogError::Nothing && b.post.is_some())
+        };
+        cache::get_or_fetch(&cache::POSTS, id, keep, fetch.boxed_local()).await
+    }
+
+    pub async fn new_post(
+        &self,
+        block: NewPostForm,
+    ) -> Result<ResponseBlock<NewPostResponse>, ApiError> {
+        let resp = self.post_authed("/api/blog/new_post", block).await;
+        cache::invalidate_lists();
+        resp
+    }
+
+    pub async fn edit_post(
+        &self,
+        block: EditPostForm,
+    ) -> Result<ResponseBlock<EditPostResponse>, ApiError> {
+        let id = block.pk;
+        let resp = self.post_authed("/api/blog/edit_post", block).await;
+        cache::invalidate_post(id);
+        resp
+    }
+
+    pub async fn delete_post(
+        &self,
+        id: i64,
+    ) -> Result<ResponseBlock<DeletePostResponse>, ApiError> {
+        let resp = self
+            .post_authed("/api/blog/delete_post", DeletePostForm { id })
+            .await;
+        cache::invalidate_post(id);
+        resp
+    }
+}
+
+impl Default for ApiClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,70 @@
+use super::{AccountError, BlogError};
+use std::fmt;
+
+/// Every way a call into the backend can fail.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ApiError {
+    /// The request never got a response (offline, DNS, CORS, ...).
+    Network(String),
+    /// The backend answered with a non-success HTTP status.
+    Status(u16),
+    /// The response body was not the JSON we expected.
+    Decode(String),
+    Account(AccountError),
+    Blog(BlogError),
+    /// No token is stored, or the stored one was rejected.
+    Unauthorized,
+}
+
+impl ApiError {
+    pub fn is_unauthorized(&self) -> bool {
+        matches!(
+            self,
+            ApiError::Unauthorized | ApiError::Blog(BlogError::AuthError)
+        )
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ApiError::Network(_) => write!(f, "Network is unreachable. Please try again later."),
+            ApiError::Status(404) => write!(f, "Not found."),
+            ApiError::Status(code) => write!(f, "Server responded with status {}.", code),
+            ApiError::Decode(_) => write!(f, "Received a malformed response from server."),
+            ApiError::Account(e) => write!(f, "{}", e),
+            ApiError::Blog(e) => write!(f, "{}", e),
+            ApiError::Unauthorized => write!(f, "Your session has expired. Please login again."),
+        }
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+impl From<reqwest::Error> for ApiError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_decode() {
+            ApiError::Decode(e.to_string())
+        } else {
+            ApiError::Network(e.to_string())
+        }
+    }
+}
+
+impl From<serde_json::Error> for ApiError {
+    fn from(e: serde_json::Error) -> Self {
+        ApiError::Decode(e.to_string())
+    }
+}
+
+impl From<AccountError> for ApiError {
+    fn from(e: AccountError) -> Self {
+        ApiError::Account(e)
+    }
+}
+
+impl From<BlogError> for ApiError {
+    fn from(e: BlogError) -> Self {
+        ApiError::Blog(e)
+    }
+}
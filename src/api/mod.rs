@@ -1,40 +1,24 @@
+pub mod cache;
+mod client;
+mod error;
+
+pub use client::ApiClient;
+pub use error::ApiError;
+
 use crate::constants::*;
-use crate::services::cookie::CookieService;
 use chrono::prelude::*;
 use serde::{Deserialize, Serialize};
 use std::fmt;
-use std::fmt::{Error, Formatter};
 use std::future::Future;
-use wasm_bindgen::prelude::*;
 use wasm_bindgen_futures::spawn_local;
 use yew::prelude::*;
-use yew::utils::origin;
-
-#[derive(Debug, Clone, PartialEq)]
-pub struct FetchError {
-    pub err: JsValue,
-}
-
-impl std::fmt::Display for FetchError {
-    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
-        std::fmt::Debug::fmt(&self.err, f)
-    }
-}
-
-impl std::error::Error for FetchError {}
-
-impl From<JsValue> for FetchError {
-    fn from(value: JsValue) -> Self {
-        FetchError { err: value }
-    }
-}
 
 #[derive(Clone, PartialEq)]
 pub enum FetchState<T> {
     NotFetching,
     Fetching,
     Success(T),
-    Failed(FetchError),
+    Failed(ApiError),
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
@@ -55,6 +39,11 @@ pub struct LoginForm {
     pub pass: String,
 }
 
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct RefreshForm {
+    pub token: String,
+}
+
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct LoginResponse {
     pub result: AccountError,
@@ -243,178 +232,3 @@ where
         link.send_message(future.await);
     });
 }
-
-pub async fn login(form: LoginForm) -> Result<ResponseBlock<LoginResponse>, anyhow::Error> {
-    let client = reqwest::Client::new();
-    let res = client
-        .post(&format!(
-            "{}/api/account_service/login",
-            origin().unwrap()
-        ))
-        .json(&form)
-        .send()
-        .await?;
-    let text = res.text().await?;
-    let info: ResponseBlock<LoginResponse> = serde_json::from_str(&text).unwrap();
-    Ok(info)
-}
-
-pub async fn register(
-    form: RegisterForm,
-) -> Result<ResponseBlock<RegisterResponse>, anyhow::Error> {
-    let client = reqwest::Client::new();
-    let res = client
-        .post(&format!(
-            "{}/api/account_service/register",
-            origin().unwrap()
-        ))
-        .json(&form)
-        .send()
-        .await?;
-    let text = res.text().await?;
-    let info: ResponseBlock<RegisterResponse> = serde_json::from_str(&text).unwrap();
-    Ok(info)
-}
-
-pub async fn get_info() -> Result<ResponseBlock<InfoResponse>, anyhow::Error> {
-    let cookie = CookieService::new();
-    let client = reqwest::Client::new();
-    let res = client
-        .get(&format!(
-            "{}/api/account_service/info?token={}",
-            origin().unwrap(),
-            cookie.get("token").unwrap_or(String::new())
-        ))
-        .send()
-        .await?;
-    let text = res.text().await?;
-    let info: ResponseBlock<InfoResponse> = serde_json::from_str(&text).unwrap();
-    Ok(info)
-}
-
-pub async fn get_info_by_pk(pk: i32) -> Result<ResponseBlock<InfoResponse>, anyhow::Error> {
-    let client = reqwest::Client::new();
-    let res = client
-        .get(&format!(
-            "{}/api/account_service/get_user?pk={}",
-            origin().unwrap(),
-            pk
-        ))
-        .send()
-        .await?;
-    let text = res.text().await?;
-    let info: ResponseBlock<InfoResponse> = serde_json::from_str(&text).unwrap();
-    Ok(info)
-}
-
-pub async fn get_post_counts() -> Result<ResponseBlock<CountPostsResponse>, anyhow::Error> {
-    let client = reqwest::Client::new();
-    let res = client
-        .get(&format!("{}/api/blog/count_posts", origin().unwrap()))
-        .send()
-        .await?;
-    let text = res.text().await?;
-    let info: ResponseBlock<CountPostsResponse> = serde_json::from_str(&text).unwrap();
-    Ok(info)
-}
-
-pub async fn recent_posts() -> Result<ResponseBlock<RecentPostsResponse>, anyhow::Error> {
-    let client = reqwest::Client::new();
-    let res = client
-        .get(&format!(
-            "{}/api/blog/recent_posts?count={}",
-            origin().unwrap(),
-            MAX_NUMBER_OF_POSTS_PREVIEW
-        ))
-        .send()
-        .await?;
-    let text = res.text().await?;
-    let info: ResponseBlock<RecentPostsResponse> = serde_json::from_str(&text).unwrap();
-    Ok(info)
-}
-
-pub async fn posts(start: i64, count: i64) -> Result<ResponseBlock<PostsResponse>, anyhow::Error> {
-    let client = reqwest::Client::new();
-    let res = client
-        .get(&format!(
-            "{}/api/blog/posts?start={}&count={}",
-            origin().unwrap(),
-            start,
-            count,
-        ))
-        .send()
-        .await?;
-    let text = res.text().await?;
-    let info: ResponseBlock<PostsResponse> = serde_json::from_str(&text).unwrap();
-    Ok(info)
-}
-
-pub async fn view_post(id: i64) -> Result<ResponseBlock<ViewPostResponse>, anyhow::Error> {
-    let cookie = CookieService::new();
-    let client = reqwest::Client::new();
-    let form = AsRequest {
-        token: cookie.get("token").unwrap_or(String::new()),
-        body: ViewPostForm { id },
-    };
-    let res = client
-        .post(&format!("{}/api/blog/view_post", origin().unwrap()))
-        .json(&form)
-        .send()
-        .await?;
-    let text = res.text().await?;
-    let info: ResponseBlock<ViewPostResponse> = serde_json::from_str(&text).unwrap();
-    Ok(info)
-}
-
-pub async fn new_post(block: NewPostForm) -> Result<ResponseBlock<NewPostResponse>, anyhow::Error> {
-    let cookie = CookieService::new();
-    let client = reqwest::Client::new();
-    let form = AsRequest {
-        token: cookie.get("token").unwrap_or(String::new()),
-        body: block,
-    };
-    let res = client
-        .post(&format!("{}/api/blog/new_post", origin().unwrap()))
-        .json(&form)
-        .send()
-        .await?;
-    let text = res.text().await?;
-    let info: ResponseBlock<NewPostResponse> = serde_json::from_str(&text).unwrap();
-    Ok(info)
-}
-
-pub async fn edit_post(
-    block: EditPostForm,
-) -> Result<ResponseBlock<EditPostResponse>, anyhow::Error> {
-    let cookie = CookieService::new();
-    let client = reqwest::Client::new();
-    let form = AsRequest {
-        token: cookie.get("token").unwrap_or(String::new()),
-        body: block,
-    };
-    let res = client
-        .post(&format!("{}/api/blog/edit_post", origin().unwrap()))
-        .json(&form)
-        .send()
-        .await?;
-    let text = res.text().await?;
-    let info: ResponseBlock<EditPostResponse> = serde_json::from_str(&text).unwrap();
-    Ok(info)
-}
-
-pub async fn delete_post(id: i64) -> Result<ResponseBlock<DeletePostResponse>, anyhow::Error> {
-    let cookie = CookieService::new();
-    let client = reqwest::Client::new();
-    let form = AsRequest {
-        token: cookie.get("token").unwrap_or(String::new()),
-        body: DeletePostForm { id },
-    };
-    let res = client
-        .post(&format!("{}/api/blog/delete_post", origin().unwrap()))
-        .json(&form)
-        .send()
-        .await?;
-    let text = res.text().await?;
-    let info: ResponseBlock<DeletePostResponse> = serde_json::from_str(&text).unwrap();
-    Ok(info)
-}
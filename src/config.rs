@@ -0,0 +1,108 @@
+use crate::api::ApiError;
+use crate::constants::*;
+use chrono_tz::Tz;
+use serde::Deserialize;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use yew::utils::document;
+
+/// Served next to `index.html`; every field is optional.
+const CONFIG_FILE: &str = "config.json";
+
+/// Deployment settings, read from `config.json` at startup.
+///
+/// Fields missing from the file fall back to the build-time environment
+/// (`BLOG_API_BASE_URL`, `BLOG_SITE_TITLE`, `BLOG_TIMEZONE`), then to the defaults in `constants`.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct Config {
+    /// Prefix for `/api/...` requests; empty means the origin the page was served from.
+    pub api_base_url: String,
+    pub site_title: String,
+    /// IANA name such as `Asia/Seoul`, used to show post dates.
+    pub timezone: String,
+    pub posts_per_page: i64,
+    /// How many recent posts the main page previews.
+    pub recent_posts: i64,
+    /// Length of post excerpts, in words.
+    pub preview_length: usize,
+    /// `"light"` or `"dark"` colors for highlighted code.
+    pub code_theme: String,
+    /// Whether highlighted code gets line numbers.
+    pub code_line_numbers: bool,
+    /// KaTeX macros every post can use, such as `\R` for `\mathbb{R}`.
+    pub math_macros: HashMap<String, String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            api_base_url: option_env!("BLOG_API_BASE_URL")
+                .unwrap_or_default()
+                .to_string(),
+            site_title: option_env!("BLOG_SITE_TITLE")
+                .unwrap_or(DEFAULT_SITE_TITLE)
+                .to_string(),
+            timezone: option_env!("BLOG_TIMEZONE")
+                .unwrap_or(DEFAULT_TIMEZONE)
+                .to_string(),
+            posts_per_page: MAX_LIST_POSTS,
+            recent_posts: MAX_NUMBER_OF_POSTS_PREVIEW,
+            preview_length: MAX_LEN_PREVIEW,
+            code_theme: String::from("light"),
+            code_line_numbers: false,
+            math_macros: HashMap::new(),
+        }
+    }
+}
+
+impl Config {
+    /// The configured timezone, or UTC if the name is unknown.
+    pub fn timezone(&self) -> Tz {
+        self.timezone.parse().unwrap_or(Tz::UTC)
+    }
+}
+
+thread_local! {
+    static CONFIG: RefCell<Rc<Config>> = RefCell::new(Rc::new(Config::default()));
+}
+
+pub fn get() -> Rc<Config> {
+    CONFIG.with(|config| config.borrow().clone())
+}
+
+pub fn set(config: Config) {
+    if config.timezone.parse::<Tz>().is_err() {
+        warn!("Unknown timezone {:?}, using UTC", config.timezone);
+    }
+    CONFIG.with(|current| *current.borrow_mut() = Rc::new(config));
+}
+
+/// Loads `config.json`; without one the build-time defaults stay in effect.
+pub async fn load() {
+    match fetch().await {
+        Ok(Some(config)) => set(config),
+        Ok(None) => {}
+        Err(e) => warn!("Ignoring {}: {:?}", CONFIG_FILE, e),
+    }
+}
+
+async fn fetch() -> Result<Option<Config>, ApiError> {
+    let url = document()
+        .base_uri()
+        .ok()
+        .flatten()
+        .and_then(|base| reqwest::Url::parse(&base).ok()?.join(CONFIG_FILE).ok());
+    let url = match url {
+        Some(url) => url,
+        None => return Ok(None),
+    };
+    let res = reqwest::Client::new().get(url).send().await?;
+    match res.status().as_u16() {
+        404 => return Ok(None),
+        code if !res.status().is_success() => return Err(ApiError::Status(code)),
+        _ => {}
+    }
+    Ok(Some(serde_json::from_str(&res.text().await?)?))
+}
@@ -1,3 +1,16 @@
 pub const MAX_LEN_PREVIEW: usize = 50;
 pub const MAX_NUMBER_OF_POSTS_PREVIEW: i64 = 5;
 pub const MAX_LIST_POSTS: i64 = 10;
+pub const SESSION_WARNING_SECS: i64 = 60;
+pub const POST_CACHE_TTL_SECS: i64 = 60;
+pub const USER_CACHE_TTL_SECS: i64 = 5 * 60;
+pub const DEFAULT_SITE_TITLE: &str = "ANEP Research";
+pub const DEFAULT_TIMEZONE: &str = "Asia/Seoul";
+pub const WORDS_PER_MINUTE: usize = 200;
+/// How long the editor waits after the last keystroke before saving a draft.
+pub const DRAFT_SAVE_DELAY_MS: u64 = 1000;
+/// How long the split editor waits after the last keystroke before updating its preview.
+pub const PREVIEW_DELAY_MS: u64 = 250;
+/// The narrowest window, in CSS pixels, the editor shows its source and preview side by side
+/// in; narrower ones only have the tabs.
+pub const SPLIT_EDITOR_MIN_WIDTH: f64 = 960.0;
@@ -0,0 +1,276 @@
+#[macro_use]
+extern crate yew;
+#[macro_use]
+extern crate yew_router;
+extern crate reqwest;
+extern crate serde;
+extern crate wasm_bindgen;
+extern crate wasm_bindgen_futures;
+extern crate web_sys;
+extern crate yew_material;
+extern crate yew_services;
+#[macro_use]
+extern crate serde_json;
+extern crate anyhow;
+#[macro_use]
+extern crate log;
+extern crate wasm_logger;
+
+pub mod api;
+pub mod config;
+pub mod constants;
+pub mod pages;
+pub mod services;
+
+use api::*;
+use constants::*;
+use serde::{Deserialize, Serialize};
+use services::store::{self, AppState, AppStore};
+use services::{jwt, router, token};
+use std::rc::Rc;
+use std::time::Duration;
+use wasm_bindgen::prelude::*;
+use yew::agent::{Bridge, Bridged};
+use yew::{
+    format::{Json, Nothing},
+    prelude::*,
+};
+use yew_material::drawer::*;
+use yew_material::list::*;
+use yew_material::text_inputs::*;
+use yew_material::top_app_bar_fixed::*;
+use yew_material::{
+    MatButton, MatFab, MatIcon, MatIconButton, MatList, MatSnackbar, MatTopAppBarFixed,
+    WeakComponentLink,
+};
+use yew_router::agent::RouteRequest;
+use yew_router::prelude::*;
+use yew_services::fetch::{FetchOptions, FetchService, FetchTask, Mode, Request, Response};
+use yew_services::timeout::{TimeoutService, TimeoutTask};
+
+/// `setTimeout` overflows past ~24.8 days, so long sessions are re-checked daily.
+const MAX_SESSION_CHECK_SECS: i64 = 24 * 60 * 60;
+
+pub struct Root {
+    link: ComponentLink<Self>,
+    is_opened: bool,
+    store: Box<dyn Bridge<AppStore>>,
+    state: Rc<AppState>,
+    search_text: String,
+    session_task: Option<TimeoutTask>,
+    session_link: WeakComponentLink<MatSnackbar>,
+    session_msg: String,
+}
+
+pub enum Msg {
+    GetNavIconClick,
+    GetOpen,
+    GetClose,
+    GetLogout,
+    StoreChanged(Rc<AppState>),
+    UpdateSearchText(InputData),
+    CheckSession,
+    SessionExpired,
+}
+
+impl Component for Root {
+    type Message = Msg;
+    type Properties = ();
+
+    fn create(_props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        let store = AppStore::bridge(link.callback(Msg::StoreChanged));
+        Self {
+            link,
+            is_opened: false,
+            store,
+            state: Rc::new(AppState::default()),
+            search_text: String::new(),
+            session_task: None,
+            session_link: WeakComponentLink::default(),
+            session_msg: String::new(),
+        }
+    }
+
+    fn update(&mut self, msg: Self::Message) -> ShouldRender {
+        match msg {
+            Msg::GetOpen => {
+                self.is_opened = true;
+                true
+            }
+            Msg::GetClose => {
+                self.is_opened = false;
+                true
+            }
+            Msg::GetNavIconClick => {
+                self.is_opened ^= true;
+                true
+            }
+            Msg::StoreChanged(state) => {
+                if state.is_logined() {
+                    // Most changes are posts being cached; only a new login or token moves
+                    // the expiry, and checking again would show the warning once more.
+                    if state.token != self.state.token || !self.state.is_logined() {
+                        self.link.send_message(Msg::CheckSession);
+                    }
+                } else {
+                    self.session_task = None;
+                }
+                self.state = state;
+                true
+            }
+            Msg::CheckSession => {
+                self.session_task = None;
+                let left = match token::load().and_then(|t| jwt::seconds_left(&t)) {
+                    Some(left) => left,
+                    None => return false,
+                };
+                if left <= 0 {
+                    self.link.send_message(Msg::SessionExpired);
+                    return false;
+                }
+                let wait = if left > SESSION_WARNING_SECS {
+                    left - SESSION_WARNING_SECS
+                } else {
+                    self.session_msg =
+                        String::from("Your session expires in a minute. Please login again.");
+                    self.session_link.show();
+                    left + 1
+                };
+                self.session_task = Some(TimeoutService::spawn(
+                    Duration::from_secs(wait.min(MAX_SESSION_CHECK_SECS) as u64),
+                    self.link.callback(|_| Msg::CheckSession),
+                ));
+                true
+            }
+            Msg::SessionExpired => {
+                self.session_msg = String::from("Your session has expired. Please login again.");
+                self.session_link.show();
+                self.link.send_message(Msg::GetLogout);
+                let mut router = RouteAgentDispatcher::<()>::new();
+                let route = Route::from(router::MainRoute::Login);
+                router.send(RouteRequest::ChangeRoute(route));
+                true
+            }
+            Msg::GetLogout => {
+                self.session_task = None;
+                self.store.send(store::Request::Logout);
+                false
+            }
+            Msg::UpdateSearchText(data) => {
+                self.search_text = data.value;
+                false
+            }
+            _ => false,
+        }
+    }
+
+    fn change(&mut self, _props: Self::Properties) -> ShouldRender {
+        false
+    }
+
+    fn view(&self) -> Html {
+        let (is_logined, nickname, level) = if let Some(user) = self.state.user.clone() {
+            (true, user.nickname, user.level)
+        } else {
+            (false, String::new(), AccountLevel::Default)
+        };
+        html! {
+            <MatDrawer open=self.is_opened drawer_type="modal" onopened=self.link.callback(|_| Msg::GetOpen) onclosed=self.link.callback(|_| Msg::GetClose)>
+                <div class="drawer-content">
+                    <div class="profile">
+                        <svg xmlns="http://www.w3.org/2000/svg" height="80" viewBox="0 0 24 24" width="80"><path d="M0 0h24v24H0z" fill="none"/><path d="M12 2C6.48 2 2 6.48 2 12s4.48 10 10 10 10-4.48 10-10S17.52 2 12 2zm0 3c1.66 0 3 1.34 3 3s-1.34 3-3 3-3-1.34-3-3 1.34-3 3-3zm0 14.2c-2.5 0-4.71-1.28-6-3.22.03-1.99 4-3.08 6-3.08 1.99 0 5.97 1.09 6 3.08-1.29 1.94-3.5 3.22-6 3.22z"/></svg>
+                        {
+                            if is_logined {
+                                html! { <h3>{&nickname}</h3> }
+                            } else {
+                                html! { <h3>{"Please login"}</h3> }
+                            }
+                        }
+                    </div>
+                    <div class="navigate-menu">
+                        <MatList>
+                            <router::MainRouterAnchor route=router::MainRoute::Main><MatListItem graphic=GraphicType::Icon>{"Home"}<mwc-icon slot="graphic">{"home"}</mwc-icon></MatListItem></router::MainRouterAnchor>
+                            <router::MainRouterAnchor route=router::MainRoute::ListPosts><MatListItem graphic=GraphicType::Icon>{"Posts"}<mwc-icon slot="graphic">{"assignment"}</mwc-icon></MatListItem></router::MainRouterAnchor>
+                            <router::MainRouterAnchor route=router::MainRoute::About><MatListItem graphic=GraphicType::Icon>{"About"}<mwc-icon slot="graphic">{"help"}</mwc-icon></MatListItem></router::MainRouterAnchor>
+                            <li divider=true></li>
+                            {
+                                if is_logined {
+                                    html! {
+                                        <>
+                                            <router::MainRouterAnchor route=router::MainRoute::Dashboard><MatListItem graphic=GraphicType::Icon>{"Dashboard"}<mwc-icon slot="graphic">{"dashboard"}</mwc-icon></MatListItem></router::MainRouterAnchor>
+                                            <span onclick=self.link.callback(|_| Msg::GetLogout)><MatListItem graphic=GraphicType::Icon>{"Log-out"} <mwc-icon slot="graphic">{"logout"}</mwc-icon></MatListItem></span>
+                                        </>
+                                    }
+                                } else {
+                                    html! {
+                                        <>
+                                            <router::MainRouterAnchor route=router::MainRoute::Login><MatListItem graphic=GraphicType::Icon>{"Login"}<mwc-icon slot="graphic">{"login"}</mwc-icon></MatListItem></router::MainRouterAnchor>
+                                            <router::MainRouterAnchor route=router::MainRoute::Register><MatListItem graphic=GraphicType::Icon>{"Register"}<mwc-icon slot="graphic">{"person_add"}</mwc-icon></MatListItem></router::MainRouterAnchor>
+                                        </>
+                                    }
+                                }
+                            }
+                        </MatList>
+                    </div>
+                </div>
+                <MatDrawerAppContent>
+                    <MatTopAppBarFixed onnavigationiconclick=self.link.callback(|_| Msg::GetNavIconClick)>
+                        <MatTopAppBarNavigationIcon>
+                            <MatIconButton icon="menu"></MatIconButton>
+                        </MatTopAppBarNavigationIcon>
+                        <MatTopAppBarTitle>
+                            {config::get().site_title.clone()}
+                        </MatTopAppBarTitle>
+                        <MatTopAppBarActionItems>
+                            //<MatTextField value=self.search_text.clone() placeholder="Search.." icon="search" oninput=self.link.callback(|data| Msg::UpdateSearchText(data))/>
+                            //<div class="fix-link"><router::MainRouterAnchor route=router::MainRoute::NewPost><MatIconButton icon="add_circle"/></router::MainRouterAnchor></div>
+                            {
+                                if is_logined {
+                                    html! { <div class="fix-link"><span onclick=self.link.callback(|_| Msg::GetLogout)><MatIconButton icon="logout"/></span></div> }
+                                } else {
+                                    html! { <div class="fix-link"><router::MainRouterAnchor route=router::MainRoute::Login><MatIconButton icon="login"/></router::MainRouterAnchor></div> }
+                                }
+                            }
+                        </MatTopAppBarActionItems>
+                    </MatTopAppBarFixed>
+                    <MatSnackbar label_text=self.session_msg.clone() snackbar_link=self.session_link.clone()/>
+                    <main id="router-outlet">
+                        <router::MainRouter render=router::MainRouter::render(Self::switch)/>
+                    </main>
+                    {
+                        if level == AccountLevel::Admin {
+                            html! { <div style="text-align: right; position: absolute; bottom: 1rem; right: 1rem;"><router::MainRouterAnchor route=router::MainRoute::Editor(-1)><MatFab icon="edit" /></router::MainRouterAnchor></div> }
+                        } else {
+                            html! {}
+                        }
+                    }
+                </MatDrawerAppContent>
+            </MatDrawer>
+        }
+    }
+}
+
+impl Root {
+    fn switch(route: router::MainRoute) -> Html {
+        match route {
+            router::MainRoute::Main => html! { <pages::main::Main/> },
+            router::MainRoute::Login => html! { <pages::login::LoginPage/> },
+            router::MainRoute::Register => html! { <pages::register::RegisterPage/> },
+            router::MainRoute::ViewPost(id) => html! { <pages::view_post::ViewPost id=id/> },
+            router::MainRoute::Editor(id) => html! { <pages::editor::EditorPage id=id/> },
+            router::MainRoute::ListPosts => html! { <pages::list_posts::ListPostsPage/> },
+            router::MainRoute::Dashboard => html! { <pages::dashboard::DashboardPage/> },
+            router::MainRoute::About => html! { <pages::about::AboutPage/> },
+            _ => html! { <pages::not_found_page::NotFoundPage/> },
+        }
+    }
+}
+
+pub fn run() {
+    wasm_logger::init(wasm_logger::Config::default());
+    wasm_bindgen_futures::spawn_local(async {
+        config::load().await;
+        yew::utils::document().set_title(&config::get().site_title);
+        yew::start_app::<Root>();
+    });
+}
@@ -1,9 +1,15 @@
 use crate::api::*;
+use crate::config;
 use crate::services::cookie::CookieService;
+use crate::services::drafts::{self, Draft};
 use crate::services::router;
+use crate::services::store::{AppState, AppStore};
+use chrono::TimeZone;
 use serde::{Deserialize, Serialize};
 use serde_json::to_string;
+use std::rc::Rc;
 use wasm_bindgen::prelude::*;
+use yew::agent::{Bridge, Bridged};
 use yew::{
     format::{Json, Nothing},
     prelude::*,
@@ -15,30 +21,76 @@ use yew_router::prelude::*;
 
 pub struct DashboardPage {
     link: ComponentLink<Self>,
-    root_link: ComponentLink<crate::Root>,
+    _store: Box<dyn Bridge<AppStore>>,
+    state: Rc<AppState>,
+    /// Drafts kept by the editor in this browser, by post id.
+    drafts: Vec<(i64, Draft)>,
 }
 
-pub enum Msg {}
+pub enum Msg {
+    StoreChanged(Rc<AppState>),
+    DeleteDraft(i64),
+}
 
 #[derive(Properties, Clone)]
 pub struct Props {}
 
+impl DashboardPage {
+    /// Drafts to pick up again in the editor or delete once stale.
+    fn view_drafts(&self) -> Html {
+        if self.drafts.is_empty() {
+            return html! {};
+        }
+        let timezone = config::get().timezone();
+        html! {
+            <div class="block">
+                <h3>{"Drafts"}</h3>
+                <ul class="drafts">
+                {
+                    for self.drafts.iter().map(|(id, draft)| {
+                        let id = *id;
+                        let post = if id == -1 { String::from("New post") } else { format!("Post {}", id) };
+                        let title = if draft.title.trim().is_empty() { "Untitled" } else { &draft.title };
+                        html! {
+                            <li>
+                                <router::MainRouterAnchor route=router::MainRoute::Editor(id)>{title}</router::MainRouterAnchor>
+                                <span class="draft-info">{format!("{} · saved {}", post, timezone.from_utc_datetime(&draft.saved_at))}</span>
+                                <span onclick=self.link.callback(move |_| Msg::DeleteDraft(id))><MatButton label="Delete"/></span>
+                            </li>
+                        }
+                    })
+                }
+                </ul>
+            </div>
+        }
+    }
+}
+
 impl Component for DashboardPage {
     type Message = Msg;
     type Properties = Props;
 
     fn create(_props: Props, link: ComponentLink<Self>) -> Self {
-        let mut any = link.get_parent().unwrap();
-        while let Some(l) = any.get_parent() {
-            any = l;
+        let store = AppStore::bridge(link.callback(Msg::StoreChanged));
+        Self {
+            link,
+            _store: store,
+            state: Rc::new(AppState::default()),
+            drafts: drafts::list(),
         }
-        let root_link: ComponentLink<crate::Root> = any.clone().downcast();
-        Self { link, root_link }
     }
 
     fn update(&mut self, msg: Self::Message) -> ShouldRender {
         match msg {
-            _ => false,
+            Msg::StoreChanged(state) => {
+                self.state = state;
+                true
+            }
+            Msg::DeleteDraft(id) => {
+                drafts::remove(id);
+                self.drafts = drafts::list();
+                true
+            }
         }
     }
 
@@ -47,13 +99,7 @@ impl Component for DashboardPage {
     }
 
     fn view(&self) -> Html {
-        let user = if let FetchState::Success(r) =
-            self.root_link.get_component().unwrap().fetch_task.clone()
-        {
-            r.body
-        } else {
-            None
-        };
+        let user = self.state.user.clone();
         html! {
             <div class="container">
                 <div class="block">
@@ -69,6 +115,7 @@ impl Component for DashboardPage {
                         }
                     }
                 </div>
+                { self.view_drafts() }
             </div>
         }
     }
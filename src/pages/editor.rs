@@ -1,57 +1,282 @@
 use crate::api::*;
+use crate::config;
+use crate::constants::{DRAFT_SAVE_DELAY_MS, PREVIEW_DELAY_MS, SPLIT_EDITOR_MIN_WIDTH};
 use crate::services::cookie::CookieService;
-use crate::services::{render, router};
+use crate::services::drafts::{self, Draft};
+use crate::services::render::{RenderCache, RenderOptions};
+use crate::services::router;
+use crate::services::store::{self, AppState, AppStore};
+use chrono::TimeZone;
+use pulldown_cmark::escape::escape_html;
 use serde::{Deserialize, Serialize};
 use serde_json::to_string;
-use std::convert::TryFrom;
+use std::cell::Cell;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
+use std::time::Duration;
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
+use yew::agent::{Bridge, Bridged};
 use yew::virtual_dom::VNode;
 use yew::{
     format::{Json, Nothing},
     prelude::*,
 };
+use yew_material::dialog::*;
 use yew_material::tabs::*;
 use yew_material::text_inputs::*;
 use yew_material::{
-    MatButton, MatDrawer, MatFormfield, MatSnackbar, MatTextField, WeakComponentLink,
+    MatButton, MatDialog, MatDrawer, MatFormfield, MatSnackbar, MatTextField, WeakComponentLink,
 };
 use yew_router::agent::RouteRequest;
 use yew_router::prelude::*;
+use yew_services::timeout::{TimeoutService, TimeoutTask};
 
 pub struct EditorPage {
     link: ComponentLink<Self>,
     props: Props,
-    //root_link: ComponentLink<crate::Root>,
     title: String,
     body: String,
     tags: String,
     error_link: WeakComponentLink<MatSnackbar>,
-    error_msg: BlogError,
+    error: Option<ApiError>,
     fetch_new_post: FetchState<ResponseBlock<NewPostResponse>>,
     fetch_edit_post: FetchState<ResponseBlock<EditPostResponse>>,
-    fetch_info: FetchState<ResponseBlock<InfoResponse>>,
+    store: Box<dyn Bridge<AppStore>>,
+    state: Rc<AppState>,
     fetch_view_post: FetchState<ResponseBlock<ViewPostResponse>>,
+    /// The tab shown: 0 to edit, 1 to preview, 2 for both side by side.
     cur: usize,
+    /// Whether the window is wide enough for the side by side tab.
+    wide: bool,
+    /// Whether each post the body links to exists, or `None` until that is known.
+    linked_posts: HashMap<i64, Option<bool>>,
+    renderer: RenderCache,
+    /// The `.markdown-body` showing the rendered body, kept across renders; see
+    /// `update_preview`.
+    preview: web_sys::Element,
+    /// Mistakes found rendering the body, shown above the preview.
+    warnings: Vec<String>,
+    /// Saves a draft once typing pauses; replaced on every change.
+    draft_task: Option<TimeoutTask>,
+    /// A draft left from an earlier visit, offered to be restored.
+    saved_draft: Option<Draft>,
+    /// The fields as last loaded or saved, to tell whether there are changes to lose.
+    saved: Fields,
+    /// What was last sent to be saved, which becomes `saved` once it is.
+    submitted: Option<Fields>,
+    /// Whether there are unsaved changes, shared with the listeners guarding them.
+    dirty: Rc<Cell<bool>>,
+    /// Set once leaving is confirmed, so nothing stops it any more.
+    leaving: bool,
+    /// Whether an entry for the editor is pushed onto the history, for the back button to
+    /// pop while the changes are confirmed to be thrown away; see `Msg::PoppedState`.
+    guarding: bool,
+    /// Set while going back to take that entry off again, which pops it without asking.
+    unguarding: bool,
+    /// Where to go once leaving is confirmed.
+    leave_to: Option<Leave>,
+    leave_dialog: WeakComponentLink<MatDialog>,
+    _listeners: Vec<Listener>,
+    /// Updates the side by side preview once typing pauses.
+    preview_task: Option<TimeoutTask>,
+    source_ref: NodeRef,
+    split_preview_ref: NodeRef,
+    /// A hidden copy of the source, a `div` per line, laid out like the textarea to find where
+    /// each line is in it.
+    mirror_ref: NodeRef,
+    /// Where each line of the body starts in the textarea, measured with the mirror.
+    line_tops: Vec<f64>,
+    /// Set when the body or the window changed since `line_tops` was measured.
+    measure: bool,
+    /// Scroll listeners of the side by side panes, while they are shown.
+    split_listeners: Vec<Listener>,
+    /// The pane scrolled to follow the other, whose scroll event is then not followed back:
+    /// `true` for the source.
+    following: Option<bool>,
+}
+
+/// What of the editor is saved, to compare against.
+#[derive(Clone, Default, PartialEq, Eq)]
+struct Fields {
+    title: String,
+    body: String,
+    /// Without spaces, as they are dropped when saving.
+    tags: String,
+}
+
+impl Fields {
+    fn new(title: &str, body: &str, tags: &str) -> Self {
+        Self {
+            title: title.to_string(),
+            body: body.to_string(),
+            tags: tags.replace(" ", ""),
+        }
+    }
+}
+
+/// Ways out of the editor held back while there are unsaved changes.
+pub enum Leave {
+    /// A link to another page of the blog.
+    To(String),
+    /// The browser's back button.
+    Back,
+}
+
+/// An event listener, removed when dropped.
+struct Listener {
+    target: web_sys::EventTarget,
+    event: &'static str,
+    capture: bool,
+    callback: Closure<dyn FnMut(web_sys::Event)>,
+}
+
+impl Listener {
+    fn new(
+        target: web_sys::EventTarget,
+        event: &'static str,
+        capture: bool,
+        callback: impl FnMut(web_sys::Event) + 'static,
+    ) -> Self {
+        let callback = Closure::wrap(Box::new(callback) as Box<dyn FnMut(web_sys::Event)>);
+        target
+            .add_event_listener_with_callback_and_bool(
+                event,
+                callback.as_ref().unchecked_ref(),
+                capture,
+            )
+            .ok();
+        Self {
+            target,
+            event,
+            capture,
+            callback,
+        }
+    }
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        self.target
+            .remove_event_listener_with_callback_and_bool(
+                self.event,
+                self.callback.as_ref().unchecked_ref(),
+                self.capture,
+            )
+            .ok();
+    }
+}
+
+/// Whether the window is wide enough to show the source and preview side by side.
+fn is_wide() -> bool {
+    web_sys::window()
+        .and_then(|window| window.inner_width().ok())
+        .and_then(|width| width.as_f64())
+        .map_or(true, |width| width >= SPLIT_EDITOR_MIN_WIDTH)
+}
+
+/// How far down `element` is in `pane`'s scrolled content, in pixels.
+fn top_in(element: &web_sys::Element, pane: &web_sys::Element) -> f64 {
+    element.get_bounding_client_rect().top() - pane.get_bounding_client_rect().top()
+        + pane.scroll_top() as f64
+}
+
+/// Where `y` of one pane is in the other, in proportion between the nearest `anchors`: pairs
+/// of places in the source and the preview showing the same line, top to bottom.
+fn follow(anchors: &[(f64, f64)], y: f64, from_source: bool) -> f64 {
+    let pick = |&(source, preview): &(f64, f64)| {
+        if from_source {
+            (source, preview)
+        } else {
+            (preview, source)
+        }
+    };
+    let next = anchors
+        .iter()
+        .position(|anchor| pick(anchor).0 > y)
+        .unwrap_or_else(|| anchors.len());
+    let before = next.checked_sub(1).map(|i| pick(&anchors[i]));
+    match (before, anchors.get(next).map(pick)) {
+        (Some((from0, to0)), Some((from1, to1))) => {
+            to0 + (y - from0) / (from1 - from0) * (to1 - to0)
+        }
+        (Some((_, to)), None) => to,
+        (None, _) => 0.0,
+    }
+}
+
+/// The path of the page a click on a link inside the blog goes to, unless it opens elsewhere
+/// or is not a click that follows the link at all.
+fn followed_link(event: &web_sys::MouseEvent) -> Option<String> {
+    if event.button() != 0
+        || event.ctrl_key()
+        || event.meta_key()
+        || event.shift_key()
+        || event.alt_key()
+    {
+        return None;
+    }
+    let anchor = event
+        .target()?
+        .dyn_into::<web_sys::Element>()
+        .ok()?
+        .closest("a[href]")
+        .ok()??;
+    if anchor
+        .get_attribute("target")
+        .map_or(false, |target| target != "_self")
+    {
+        return None;
+    }
+    let href = anchor.get_attribute("href")?;
+    router::route_of(&href).map(|_| href)
+}
+
+/// Goes to `href`, a path inside the blog.
+fn go_to(href: String) {
+    let mut router = RouteAgentDispatcher::<()>::new();
+    router.send(RouteRequest::ChangeRoute(Route {
+        route: href,
+        state: (),
+    }));
 }
 
 pub enum Msg {
     UpdateTitle(InputData),
     UpdateBody(InputData),
     UpdateTags(InputData),
-    GetInfo,
     GetNewPost,
     GetEditPost,
     GetViewPost,
     ReceiveEditPostResponse(FetchState<ResponseBlock<EditPostResponse>>),
     ReceiveNewPostResponse(FetchState<ResponseBlock<NewPostResponse>>),
-    ReceiveInfoResponse(FetchState<ResponseBlock<InfoResponse>>),
+    StoreChanged(Rc<AppState>),
     ReceiveViewPostResponse(FetchState<ResponseBlock<ViewPostResponse>>),
     ShowError,
     GoMain,
     GoLogin,
     GoPost,
     SetCur(usize),
+    /// Looks up a post the body links to, unless that is done already.
+    CheckLinkedPost(i64),
+    ReceiveLinkedPost(i64, FetchState<ResponseBlock<ViewPostResponse>>),
+    SaveDraft,
+    RestoreDraft,
+    DiscardDraft,
+    /// Asks whether to throw the unsaved changes away and leave.
+    ConfirmLeave(Leave),
+    Leave,
+    Stay,
+    /// The browser went back or forward in its history.
+    PoppedState,
+    /// The window was resized, which may leave too little room to show both side by side.
+    Resized,
+    /// Renders the body again for the side by side preview.
+    RefreshPreview,
+    /// One of the side by side panes scrolled: `true` for the source.
+    SplitScrolled(bool),
 }
 
 #[derive(Properties, Clone)]
@@ -59,58 +284,408 @@ pub struct Props {
     pub id: i64,
 }
 
+impl EditorPage {
+    /// Saves a draft after `DRAFT_SAVE_DELAY_MS` without further changes.
+    fn schedule_draft(&mut self) {
+        self.draft_task = Some(TimeoutService::spawn(
+            Duration::from_millis(DRAFT_SAVE_DELAY_MS),
+            self.link.callback(|_| Msg::SaveDraft),
+        ));
+    }
+
+    fn save_draft(&mut self) {
+        self.draft_task = None;
+        let draft = Draft::new(self.title.clone(), self.body.clone(), self.tags.clone());
+        drafts::save(self.props.id, &draft);
+    }
+
+    fn fields(&self) -> Fields {
+        Fields::new(&self.title, &self.body, &self.tags)
+    }
+
+    /// Compares the fields against those saved and, once they differ, keeps an entry pushed
+    /// for the back button to pop without leaving, until they are saved or thrown away. None
+    /// is pushed while asking whether to leave, so going back after all leaves.
+    fn refresh_dirty(&mut self) {
+        let dirty = !self.leaving && self.fields() != self.saved;
+        self.dirty.set(dirty);
+        if dirty && !self.guarding && !self.unguarding && self.leave_to.is_none() {
+            let history = web_sys::window().unwrap().history().unwrap();
+            if history.push_state(&JsValue::NULL, "").is_ok() {
+                self.guarding = true;
+            }
+        } else if !dirty && self.guarding {
+            self.unguard();
+        }
+    }
+
+    /// Goes back to take the entry pushed by `refresh_dirty` off the history, so the back
+    /// button does not stop at the editor once more.
+    fn unguard(&mut self) {
+        let history = web_sys::window().unwrap().history().unwrap();
+        if history.back().is_ok() {
+            self.guarding = false;
+            self.unguarding = true;
+        }
+    }
+
+    /// Goes to `route`, after the guarding entry is off the history if it is being taken off,
+    /// as it is once a save clears the changes; see `Msg::PoppedState`.
+    fn go(&mut self, route: router::MainRoute) {
+        let href = router::path(route);
+        if self.unguarding {
+            self.leave_to = Some(Leave::To(href));
+        } else {
+            go_to(href);
+        }
+    }
+
+    /// Forgets the draft once the post it is of has been saved.
+    fn drop_draft(&mut self) {
+        self.draft_task = None;
+        drafts::remove(self.props.id);
+    }
+
+    /// Offers to restore the saved draft, once the post it would replace is loaded.
+    fn view_saved_draft(&self, loaded: bool) -> Html {
+        let draft = match &self.saved_draft {
+            Some(draft) if loaded => draft,
+            _ => return html! {},
+        };
+        let saved_at = config::get().timezone().from_utc_datetime(&draft.saved_at);
+        html! {
+            <div class="field draft-restore">
+                <span>{format!("You have a draft of this post from {}.", saved_at)}</span>
+                <span onclick=self.link.callback(|_| Msg::RestoreDraft)><MatButton label="Restore"/></span>
+                <span onclick=self.link.callback(|_| Msg::DiscardDraft)><MatButton label="Discard"/></span>
+            </div>
+        }
+    }
+
+    /// Posts the body links to that turned out not to exist, looking up those not checked yet.
+    fn missing_posts(&self, post_links: &[i64]) -> Vec<i64> {
+        let mut missing = vec![];
+        for &id in post_links {
+            match self.linked_posts.get(&id) {
+                Some(Some(false)) => missing.push(id),
+                Some(_) => {}
+                None => self.link.send_message(Msg::CheckLinkedPost(id)),
+            }
+        }
+        missing
+    }
+
+    /// Whether the source and preview are shown side by side.
+    fn split(&self) -> bool {
+        self.cur == 2 && self.wide
+    }
+
+    /// Lays the body out in the mirror, a line per `div`, to find where each line starts.
+    fn measure_lines(&mut self) {
+        let (source, mirror) = match (
+            self.source_ref.cast::<web_sys::Element>(),
+            self.mirror_ref.cast::<web_sys::Element>(),
+        ) {
+            (Some(source), Some(mirror)) => (source, mirror),
+            _ => return,
+        };
+        let mut html = String::new();
+        for line in self.body.split('\n') {
+            html.push_str("<div>");
+            // An empty line would be no line at all.
+            let line = if line.is_empty() { "\u{200B}" } else { line };
+            escape_html(&mut html, line).unwrap();
+            html.push_str("</div>");
+        }
+        mirror
+            .set_attribute("style", &format!("width: {}px", source.client_width()))
+            .ok();
+        mirror.set_inner_html(&html);
+        let lines = mirror.children();
+        self.line_tops = (0..lines.length())
+            .filter_map(|i| lines.item(i))
+            .map(|line| top_in(&line, &mirror))
+            .collect();
+        self.measure = false;
+    }
+
+    /// Scrolls one side by side pane to show the same lines as the other, going by the source
+    /// lines the preview's blocks are marked with.
+    fn follow_scroll(&mut self, from_source: bool) {
+        let (source, pane) = match (
+            self.source_ref.cast::<web_sys::Element>(),
+            self.split_preview_ref.cast::<web_sys::Element>(),
+        ) {
+            (Some(source), Some(pane)) => (source, pane),
+            _ => return,
+        };
+        let mut anchors = vec![(0.0, 0.0)];
+        if let Ok(marks) = self.preview.query_selector_all(".source-line") {
+            for i in 0..marks.length() {
+                let mark = match marks
+                    .item(i)
+                    .and_then(|node| node.dyn_into::<web_sys::Element>().ok())
+                {
+                    Some(mark) => mark,
+                    None => continue,
+                };
+                let line = mark
+                    .get_attribute("data-source-line")
+                    .and_then(|line| line.parse::<usize>().ok());
+                if let Some(&source_top) = line.and_then(|line| self.line_tops.get(line)) {
+                    let (last_source, last_preview) = anchors[anchors.len() - 1];
+                    let preview_top = top_in(&mark, &pane);
+                    // Blocks out of order, such as in a footnote, would make it go back.
+                    if source_top > last_source && preview_top > last_preview {
+                        anchors.push((source_top, preview_top));
+                    }
+                }
+            }
+        }
+        let ends = (source.scroll_height() as f64, pane.scroll_height() as f64);
+        let (last_source, last_preview) = anchors[anchors.len() - 1];
+        if ends.0 > last_source && ends.1 > last_preview {
+            anchors.push(ends);
+        }
+        let (from, to) = if from_source {
+            (&source, &pane)
+        } else {
+            (&pane, &source)
+        };
+        let y = follow(&anchors, from.scroll_top() as f64, from_source);
+        if (to.scroll_top() as f64 - y).abs() >= 1.0 {
+            self.following = Some(!from_source);
+            to.set_scroll_top(y.round() as i32);
+        }
+    }
+
+    /// Renders the body into `preview`, replacing only the blocks whose HTML changed.
+    fn update_preview(&mut self) {
+        self.preview_task = None;
+        self.measure = true;
+        let options = RenderOptions {
+            source_lines: true,
+            ..RenderOptions::configured()
+        };
+        let mut post = self.renderer.render_with(self.body.clone(), options);
+        let missing = self.missing_posts(&post.post_links);
+        let fresh = web_sys::window()
+            .unwrap()
+            .document()
+            .unwrap()
+            .create_element("div")
+            .unwrap();
+        fresh.set_inner_html(&post.html);
+        for id in missing {
+            let href = router::path(router::MainRoute::ViewPost(id));
+            let selector = format!("a.post-link[href='{}']", href);
+            if let Ok(links) = fresh.query_selector_all(&selector) {
+                for i in 0..links.length() {
+                    if let Some(link) = links
+                        .item(i)
+                        .and_then(|node| node.dyn_into::<web_sys::Element>().ok())
+                    {
+                        link.set_attribute("class", "post-link broken").ok();
+                    }
+                }
+            }
+            post.warnings.push(format!(
+                "[[post:{}]] links to a post that does not exist",
+                id
+            ));
+        }
+        patch_children(&self.preview, &fresh);
+        self.warnings = post.warnings;
+    }
+
+    /// What the author should fix in the body, such as a macro KaTeX cannot use.
+    fn view_warnings(&self, warnings: &[String]) -> Html {
+        if warnings.is_empty() {
+            return html! {};
+        }
+        html! {
+            <ul class="render-warnings">
+                { for warnings.iter().map(|warning| html! { <li>{warning}</li> }) }
+            </ul>
+        }
+    }
+}
+
+/// Tells nodes apart by their markup, or text for text nodes.
+fn node_key(node: &web_sys::Node) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    node.node_type().hash(&mut hasher);
+    match node.dyn_ref::<web_sys::Element>() {
+        Some(element) => element.outer_html().hash(&mut hasher),
+        None => node.text_content().hash(&mut hasher),
+    }
+    hasher.finish()
+}
+
+/// Gives `container` the children of `fresh`, keeping those it has already where they are the
+/// same, so only new and changed blocks are laid out and math that did not change is not
+/// drawn again.
+fn patch_children(container: &web_sys::Element, fresh: &web_sys::Element) {
+    let mut kept: HashMap<u64, Vec<web_sys::Node>> = HashMap::new();
+    let old = container.child_nodes();
+    for i in (0..old.length()).rev() {
+        if let Some(node) = old.item(i) {
+            kept.entry(node_key(&node)).or_default().push(node);
+        }
+    }
+    // The list is live, so the new children are taken out of it before any of them moves.
+    let new = fresh.child_nodes();
+    let new: Vec<web_sys::Node> = (0..new.length()).filter_map(|i| new.item(i)).collect();
+    let mut next = container.first_child();
+    for node in new {
+        let node = kept
+            .get_mut(&node_key(&node))
+            .and_then(Vec::pop)
+            .unwrap_or(node);
+        match next {
+            Some(ref at) if at.is_same_node(Some(&node)) => next = at.next_sibling(),
+            _ => {
+                container.insert_before(&node, next.as_ref()).ok();
+            }
+        }
+    }
+    while let Some(node) = next {
+        next = node.next_sibling();
+        container.remove_child(&node).ok();
+    }
+}
+
 impl Component for EditorPage {
     type Message = Msg;
     type Properties = Props;
 
     fn create(props: Props, link: ComponentLink<Self>) -> Self {
-        //let mut any = link.get_parent().unwrap();
-        //while let Some(l) = any.get_parent() {
-        //    any = l;
-        //}
-        //let root_link: ComponentLink<crate::Root> = any.clone().downcast();
+        let store = AppStore::bridge(link.callback(Msg::StoreChanged));
+        let preview = web_sys::window()
+            .unwrap()
+            .document()
+            .unwrap()
+            .create_element("div")
+            .unwrap();
+        preview.set_class_name("markdown-body");
+        let saved_draft = drafts::load(props.id);
+        let dirty = Rc::new(Cell::new(false));
+        let window = web_sys::window().unwrap();
+        let document = window.document().unwrap();
+        let listeners = vec![
+            {
+                let dirty = dirty.clone();
+                Listener::new(window.clone().into(), "beforeunload", false, move |e| {
+                    if dirty.get() {
+                        e.prevent_default();
+                        if let Some(e) = e.dyn_ref::<web_sys::BeforeUnloadEvent>() {
+                            e.set_return_value("");
+                        }
+                    }
+                })
+            },
+            {
+                // Captured on the way down, before the link's own handler changes the route.
+                let dirty = dirty.clone();
+                let link = link.clone();
+                Listener::new(document.into(), "click", true, move |e| {
+                    let href = e.dyn_ref::<web_sys::MouseEvent>().and_then(followed_link);
+                    if let (true, Some(href)) = (dirty.get(), href) {
+                        e.prevent_default();
+                        e.stop_propagation();
+                        link.send_message(Msg::ConfirmLeave(Leave::To(href)));
+                    }
+                })
+            },
+            {
+                let link = link.clone();
+                Listener::new(window.clone().into(), "popstate", false, move |_| {
+                    link.send_message(Msg::PoppedState)
+                })
+            },
+            {
+                let link = link.clone();
+                Listener::new(window.into(), "resize", false, move |_| {
+                    link.send_message(Msg::Resized)
+                })
+            },
+        ];
         Self {
             link,
             props,
-            //root_link,
             title: String::new(),
             body: String::new(),
             tags: String::new(),
             error_link: WeakComponentLink::default(),
-            error_msg: BlogError::Nothing,
+            error: None,
             fetch_new_post: FetchState::NotFetching,
             fetch_edit_post: FetchState::NotFetching,
-            fetch_info: FetchState::NotFetching,
+            store,
+            state: Rc::new(AppState::default()),
             fetch_view_post: FetchState::NotFetching,
             cur: 0,
+            wide: is_wide(),
+            linked_posts: HashMap::new(),
+            renderer: RenderCache::default(),
+            preview,
+            warnings: vec![],
+            draft_task: None,
+            saved_draft,
+            saved: Fields::default(),
+            submitted: None,
+            dirty,
+            leaving: false,
+            guarding: false,
+            unguarding: false,
+            leave_to: None,
+            leave_dialog: WeakComponentLink::default(),
+            _listeners: listeners,
+            preview_task: None,
+            source_ref: NodeRef::default(),
+            split_preview_ref: NodeRef::default(),
+            mirror_ref: NodeRef::default(),
+            line_tops: vec![],
+            measure: true,
+            split_listeners: vec![],
+            following: None,
         }
     }
 
     fn update(&mut self, msg: Self::Message) -> ShouldRender {
-        match msg {
+        let render = match msg {
             Msg::UpdateTitle(s) => {
                 self.title = s.value;
+                self.schedule_draft();
                 false
             }
             Msg::UpdateBody(s) => {
                 self.body = s.value;
+                self.schedule_draft();
+                if self.split() {
+                    self.preview_task = Some(TimeoutService::spawn(
+                        Duration::from_millis(PREVIEW_DELAY_MS),
+                        self.link.callback(|_| Msg::RefreshPreview),
+                    ));
+                }
                 false
             }
             Msg::UpdateTags(s) => {
                 self.tags = s.value;
+                self.schedule_draft();
                 false
             }
             Msg::GetEditPost => {
                 if self.title.len() == 0 {
-                    self.error_msg = BlogError::TooShortTitle;
+                    self.error = Some(BlogError::TooShortTitle.into());
                     true
                 } else if self.body.len() == 0 {
-                    self.error_msg = BlogError::TooShortBody;
+                    self.error = Some(BlogError::TooShortBody.into());
                     true
                 } else if !self.tags.is_ascii() {
-                    self.error_msg = BlogError::InvalidTags;
+                    self.error = Some(BlogError::InvalidTags.into());
                     true
                 } else {
+                    self.submitted = Some(self.fields());
                     let t = self.tags.replace(" ", "");
                     let tags_vec: Vec<String> = t.split(",").map(|s| s.to_string()).collect();
                     let form = EditPostForm {
@@ -120,11 +695,9 @@ impl Component for EditorPage {
                         tag: tags_vec,
                     };
                     let future = async move {
-                        match edit_post(form).await {
+                        match ApiClient::new().edit_post(form).await {
                             Ok(info) => Msg::ReceiveEditPostResponse(FetchState::Success(info)),
-                            Err(_) => Msg::ReceiveEditPostResponse(FetchState::Failed(
-                                FetchError::from(JsValue::FALSE),
-                            )),
+                            Err(e) => Msg::ReceiveEditPostResponse(FetchState::Failed(e)),
                         }
                     };
                     send_future(self.link.clone(), future);
@@ -133,15 +706,16 @@ impl Component for EditorPage {
             }
             Msg::GetNewPost => {
                 if self.title.len() == 0 {
-                    self.error_msg = BlogError::TooShortTitle;
+                    self.error = Some(BlogError::TooShortTitle.into());
                     true
                 } else if self.body.len() == 0 {
-                    self.error_msg = BlogError::TooShortBody;
+                    self.error = Some(BlogError::TooShortBody.into());
                     true
                 } else if !self.tags.is_ascii() {
-                    self.error_msg = BlogError::InvalidTags;
+                    self.error = Some(BlogError::InvalidTags.into());
                     true
                 } else {
+                    self.submitted = Some(self.fields());
                     let t = self.tags.replace(" ", "");
                     let tags_vec: Vec<String> = t.split(",").map(|s| s.to_string()).collect();
                     let form = NewPostForm {
@@ -150,59 +724,52 @@ impl Component for EditorPage {
                         tag: tags_vec,
                     };
                     let future = async move {
-                        match new_post(form).await {
+                        match ApiClient::new().new_post(form).await {
                             Ok(info) => Msg::ReceiveNewPostResponse(FetchState::Success(info)),
-                            Err(_) => Msg::ReceiveNewPostResponse(FetchState::Failed(
-                                FetchError::from(JsValue::FALSE),
-                            )),
+                            Err(e) => Msg::ReceiveNewPostResponse(FetchState::Failed(e)),
                         }
                     };
                     send_future(self.link.clone(), future);
                     false
                 }
             }
-            Msg::GetInfo => {
-                let future = async move {
-                    match get_info().await {
-                        Ok(info) => Msg::ReceiveInfoResponse(FetchState::Success(info)),
-                        Err(_) => Msg::ReceiveInfoResponse(FetchState::Failed(FetchError::from(
-                            JsValue::FALSE,
-                        ))), // TODO
-                    }
-                };
-                send_future(self.link.clone(), future);
-                false
-            }
             Msg::GetViewPost => {
                 let id = self.props.id;
                 self.fetch_view_post = FetchState::Fetching;
                 let future = async move {
-                    match view_post(id).await {
+                    match ApiClient::new().view_post(id).await {
                         Ok(info) => Msg::ReceiveViewPostResponse(FetchState::Success(info)),
-                        Err(_) => Msg::ReceiveViewPostResponse(FetchState::Failed(
-                            FetchError::from(JsValue::FALSE),
-                        )),
+                        Err(e) => Msg::ReceiveViewPostResponse(FetchState::Failed(e)),
                     }
                 };
                 send_future(self.link.clone(), future);
                 false
             }
-            Msg::ReceiveInfoResponse(data) => {
-                self.fetch_info = data;
+            Msg::StoreChanged(state) => {
+                self.state = state;
                 true
             }
             Msg::ReceiveEditPostResponse(data) => {
                 if let FetchState::Success(resp) = data.clone() {
                     if let Some(body) = resp.body {
                         match body.error {
-                            BlogError::Nothing => {}
+                            BlogError::Nothing => {
+                                self.store.send(store::Request::ForgetPost(self.props.id));
+                                self.saved = self.submitted.take().unwrap_or_default();
+                                self.drop_draft();
+                                // Handled after `refresh_dirty` starts taking the guarding
+                                // entry off, which `go` waits for.
+                                self.link.send_message(Msg::GoPost);
+                            }
                             _ => {
-                                self.error_msg = body.error;
+                                self.error = Some(body.error.into());
                             }
                         }
                     } else {
-                        self.error_msg = BlogError::NetworkError;
+                        self.error = Some(BlogError::NetworkError.into());
                     }
+                } else if let FetchState::Failed(e) = data.clone() {
+                    self.error = Some(e);
                 }
                 self.fetch_edit_post = data;
                 true
@@ -211,14 +778,20 @@ impl Component for EditorPage {
                 if let FetchState::Success(resp) = data.clone() {
                     if let Some(body) = resp.body {
                         match body.error {
-                            BlogError::Nothing => {}
+                            BlogError::Nothing => {
+                                self.saved = self.submitted.take().unwrap_or_default();
+                                self.drop_draft();
+                                self.link.send_message(Msg::GoMain);
+                            }
                             _ => {
-                                self.error_msg = body.error;
+                                self.error = Some(body.error.into());
                             }
                         }
                     } else {
-                        self.error_msg = BlogError::NetworkError;
+                        self.error = Some(BlogError::NetworkError.into());
                     }
+                } else if let FetchState::Failed(e) = data.clone() {
+                    self.error = Some(e);
                 }
                 self.fetch_new_post = data;
                 true
@@ -228,9 +801,24 @@ impl Component for EditorPage {
                 if let FetchState::Success(post) = data {
                     if let Some(body) = post.body {
                         if let Some(p) = body.post {
+                            self.store
+                                .send(store::Request::CachePost(self.props.id, p.clone()));
                             self.title = p.title;
                             self.body = p.body;
                             self.tags = p.tags.join(",");
+                            self.saved = self.fields();
+                            // A draft with nothing the post lacks is not worth offering.
+                            let same = self.saved_draft.as_ref().map_or(false, |draft| {
+                                (&draft.title, &draft.body, &draft.tags)
+                                    == (&self.title, &self.body, &self.tags)
+                            });
+                            if same {
+                                self.saved_draft = None;
+                                drafts::remove(self.props.id);
+                            }
+                            if self.cur != 0 {
+                                self.update_preview();
+                            }
                         }
                     }
                 }
@@ -241,28 +829,190 @@ impl Component for EditorPage {
                 false
             }
             Msg::GoMain => {
-                let mut router = RouteAgentDispatcher::<()>::new();
-                let route = Route::from(router::MainRoute::Main);
-                router.send(RouteRequest::ChangeRoute(route));
+                self.go(router::MainRoute::Main);
                 false
             }
             Msg::GoLogin => {
-                let mut router = RouteAgentDispatcher::<()>::new();
-                let route = Route::from(router::MainRoute::Login);
-                router.send(RouteRequest::ChangeRoute(route));
+                self.go(router::MainRoute::Login);
                 false
             }
             Msg::GoPost => {
-                let mut router = RouteAgentDispatcher::<()>::new();
-                let route = Route::from(router::MainRoute::ViewPost(self.props.id));
-                router.send(RouteRequest::ChangeRoute(route));
+                self.go(router::MainRoute::ViewPost(self.props.id));
+                false
+            }
+            Msg::CheckLinkedPost(id) => {
+                if self.linked_posts.contains_key(&id) {
+                    return false;
+                }
+                self.linked_posts.insert(id, None);
+                let future = async move {
+                    match ApiClient::new().view_post(id).await {
+                        Ok(info) => Msg::ReceiveLinkedPost(id, FetchState::Success(info)),
+                        Err(e) => Msg::ReceiveLinkedPost(id, FetchState::Failed(e)),
+                    }
+                };
+                send_future(self.link.clone(), future);
                 false
             }
+            Msg::ReceiveLinkedPost(id, data) => {
+                // A failed request says nothing about the post, so it is not flagged.
+                if let FetchState::Success(resp) = data {
+                    let exists = resp.body.map_or(false, |body| body.post.is_some());
+                    self.linked_posts.insert(id, Some(exists));
+                    if self.cur != 0 {
+                        self.update_preview();
+                    }
+                }
+                true
+            }
             Msg::SetCur(idx) => {
-                self.cur = idx;
+                // There is no side by side tab to be on in a narrow window.
+                self.cur = if idx == 2 && !self.wide { 0 } else { idx };
+                self.split_listeners.clear();
+                if self.cur != 0 {
+                    self.update_preview();
+                }
+                true
+            }
+            Msg::SaveDraft => {
+                self.save_draft();
+                false
+            }
+            Msg::RestoreDraft => {
+                if let Some(draft) = self.saved_draft.take() {
+                    self.title = draft.title;
+                    self.body = draft.body;
+                    self.tags = draft.tags;
+                    self.save_draft();
+                    if self.cur != 0 {
+                        self.update_preview();
+                    }
+                }
+                true
+            }
+            Msg::DiscardDraft => {
+                // Unless it was replaced already by what has been typed since.
+                if self.saved_draft.take() == drafts::load(self.props.id) {
+                    drafts::remove(self.props.id);
+                }
+                true
+            }
+            Msg::ConfirmLeave(leave) => {
+                self.leave_to = Some(leave);
+                self.leave_dialog.show();
+                false
+            }
+            Msg::Leave => {
+                self.leaving = true;
+                match self.leave_to.take() {
+                    // The page is gone to once the guarding entry is off the history, as going
+                    // back meanwhile would undo it; see `Msg::PoppedState`.
+                    Some(Leave::To(href)) if self.guarding => {
+                        self.leave_to = Some(Leave::To(href));
+                        self.unguard();
+                    }
+                    Some(Leave::To(href)) => go_to(href),
+                    Some(Leave::Back) => {
+                        web_sys::window().unwrap().history().unwrap().back().ok();
+                    }
+                    None => {}
+                }
+                false
+            }
+            Msg::Stay => {
+                // Going back took the guarding entry off the history; `refresh_dirty` pushes
+                // another.
+                self.leave_to = None;
+                false
+            }
+            Msg::PoppedState => {
+                if self.unguarding {
+                    self.unguarding = false;
+                    if let Some(Leave::To(href)) = self.leave_to.take() {
+                        go_to(href);
+                    }
+                    return false;
+                }
+                let location = web_sys::window().unwrap().location();
+                let here = router::path(router::MainRoute::Editor(self.props.id));
+                let popped = self.guarding && location.pathname().ok() == Some(here);
+                if self.leaving || !popped {
+                    return false;
+                }
+                self.guarding = false;
+                if self.dirty.get() {
+                    self.leave_to = Some(Leave::Back);
+                    self.leave_dialog.show();
+                } else {
+                    // Saved since the entry was pushed; go back as asked.
+                    web_sys::window().unwrap().history().unwrap().back().ok();
+                }
+                false
+            }
+            Msg::Resized => {
+                let wide = is_wide();
+                if wide == self.wide {
+                    self.measure = true;
+                    return false;
+                }
+                self.wide = wide;
+                if !wide && self.cur == 2 {
+                    self.cur = 0;
+                    self.split_listeners.clear();
+                }
+                true
+            }
+            Msg::RefreshPreview => {
+                self.update_preview();
                 true
             }
+            Msg::SplitScrolled(from_source) => {
+                // Following the other pane scrolls this one, which is not followed back.
+                if self.following.take() == Some(from_source) {
+                    return false;
+                }
+                if self.measure {
+                    self.measure_lines();
+                }
+                self.follow_scroll(from_source);
+                false
+            }
             _ => false,
+        };
+        self.refresh_dirty();
+        render
+    }
+
+    fn rendered(&mut self, _first_render: bool) {
+        if !self.split() {
+            return;
+        }
+        if self.split_listeners.is_empty() {
+            let panes = [
+                (self.source_ref.cast::<web_sys::Element>(), true),
+                (self.split_preview_ref.cast::<web_sys::Element>(), false),
+            ];
+            for (pane, from_source) in panes.iter().cloned() {
+                if let Some(pane) = pane {
+                    let link = self.link.clone();
+                    self.split_listeners.push(Listener::new(
+                        pane.into(),
+                        "scroll",
+                        false,
+                        move |_| link.send_message(Msg::SplitScrolled(from_source)),
+                    ));
+                }
+            }
+        }
+        if self.measure {
+            self.measure_lines();
+        }
+    }
+
+    fn destroy(&mut self) {
+        // Leaving the page saves what has not been yet.
+        if self.draft_task.is_some() {
+            self.save_draft();
         }
     }
 
@@ -287,60 +1037,37 @@ impl Component for EditorPage {
         } else {
             None
         };
-        if let FetchState::Success(r) = self.fetch_info.clone() {
-            if !r.status {
-                self.link.send_message(Msg::GoLogin);
-            }
-            if self.props.id != -1 {
-                if let Some(body) = post.clone() {
-                    if let Some(user) = r.body {
+        if self.state.loaded {
+            if let Some(user) = self.state.user.clone() {
+                if self.props.id != -1 {
+                    if let Some(body) = post.clone() {
                         if body.author != user.pk as i32 {
                             self.link.send_message(Msg::GoLogin);
                         }
-                    } else {
-                        self.link.send_message(Msg::GoLogin);
                     }
+                } else if user.level != AccountLevel::Admin {
+                    self.link.send_message(Msg::GoLogin);
                 }
             } else {
-                if let Some(user) = r.body {
-                    if user.level != AccountLevel::Admin {
-                        self.link.send_message(Msg::GoLogin);
-                    }
-                }
-            }
-        } else if let FetchState::NotFetching = self.fetch_info.clone() {
-            self.link.send_message(Msg::GetInfo);
-        }
-        if let FetchState::Success(resp) = self.fetch_new_post.clone() {
-            if let Some(body) = resp.body {
-                match body.error {
-                    BlogError::Nothing => {
-                        self.link.send_message(Msg::GoMain);
-                    }
-                    _ => {}
-                }
-            }
-        }
-        if self.error_msg != BlogError::Nothing {
-            self.link.send_message(Msg::ShowError);
-        }
-        if let FetchState::Success(resp) = self.fetch_edit_post.clone() {
-            if let Some(body) = resp.body {
-                match body.error {
-                    BlogError::Nothing => {
-                        self.link.send_message(Msg::GoPost);
-                    }
-                    _ => {}
-                }
+                self.link.send_message(Msg::GoLogin);
             }
         }
-        if self.error_msg != BlogError::Nothing {
+        if self.error.is_some() {
             self.link.send_message(Msg::ShowError);
         }
         let id = self.props.id;
         html! {
             <div class="container">
-                <MatSnackbar label_text=&format!("Failed to create post: {}", self.error_msg) snackbar_link=self.error_link.clone()/>
+                <MatDialog heading="Leave without saving?" dialog_link=self.leave_dialog.clone() onclosing=self.link.callback(|action: String| match action.as_str() { "leave" => Msg::Leave, _ => Msg::Stay })>
+                    {"Your changes to this post are not saved. A draft of them is kept in this browser."}
+                    <MatDialogAction action_type=ActionType::Secondary action="stay">
+                        <MatButton label="Stay" />
+                    </MatDialogAction>
+                    <MatDialogAction action_type=ActionType::Primary action="leave">
+                        <MatButton label="Leave" />
+                    </MatDialogAction>
+                </MatDialog>
+                <MatSnackbar label_text=&format!("Failed to create post: {}", self.error.as_ref().map(ToString::to_string).unwrap_or_default()) snackbar_link=self.error_link.clone()/>
                 <div class="form-fill">
                     <div class="field">
                         <h3>{ if self.props.id == -1 { "New post to blog" } else { "Edit a post" }}</h3>
@@ -348,44 +1075,41 @@ impl Component for EditorPage {
                     <div class="field">
                         <MatTextField required=true fullwidth=true outlined=true label="Title" value=self.title.clone() oninput=self.link.callback(|s| Msg::UpdateTitle(s))/>
                     </div>
+                    { self.view_saved_draft(self.props.id == -1 || post.is_some()) }
                     <div class="field">
-                    <MatTabBar onactivated=self.link.callback(|idx| {
+                    <MatTabBar active_index=self.cur as u32 onactivated=self.link.callback(|idx| {
                         Msg::SetCur(idx)
                     })>
                         <MatTab label="Edit"/>
                         <MatTab label="Preview"/>
+                        { if self.wide { html! { <MatTab label="Side by side"/> } } else { html! {} } }
                     </MatTabBar>
                     </div>
                     <div class="field">
                     {
-                        if self.cur == 0 {
+                        if self.split() {
+                            html! {
+                                <div class="editor-split">
+                                    <textarea ref=self.source_ref.clone() class="split-source" aria-label="Body" value=self.body.clone() oninput=self.link.callback(|s| Msg::UpdateBody(s))/>
+                                    <div ref=self.mirror_ref.clone() class="split-mirror" aria-hidden="true"></div>
+                                    <div ref=self.split_preview_ref.clone() class="split-preview">
+                                        { self.view_warnings(&self.warnings) }
+                                        { VNode::VRef(self.preview.clone().into()) }
+                                    </div>
+                                </div>
+                            }
+                        } else if self.cur != 1 {
                             html! {
                                 <div style="height: 300px;">
                                     <MatTextArea required=true fullheight=true fullwidth=true outlined=true label="Body" value=self.body.clone() oninput=self.link.callback(|s| Msg::UpdateBody(s))/>
                                 </div>
                             }
                         } else {
-                            let html_output = render::render(self.body.clone());
-                            let window = web_sys::window().unwrap();
-                            let document = window.document().unwrap();
-                            let html_document = document.dyn_into::<web_sys::HtmlDocument>().unwrap();
-                            let render: web_sys::HtmlDivElement = html_document
-                                .create_element("div")
-                                .unwrap()
-                                .dyn_into::<web_sys::HtmlDivElement>()
-                                .unwrap();
-                            render.set_inner_html(&html_output);
                             html! {
-                                <div class="markdown-body">
-                                {if let Ok(node) = web_sys::Node::try_from(render) {
-                                    let vnode = VNode::VRef(node);
-                                    vnode
-                                  } else {
-                                    html! {
-                                      <div class="error">{"error"}</div>
-                                    }
-                                  }}
-                                </div>
+                                <>
+                                { self.view_warnings(&self.warnings) }
+                                { VNode::VRef(self.preview.clone().into()) }
+                                </>
                             }
                         }
                     }
@@ -1,9 +1,11 @@
 use crate::api::*;
-use crate::constants::*;
+use crate::config;
 use crate::services::cookie::CookieService;
+use crate::services::render::{self, RenderedPost};
 use crate::services::router;
 use serde::{Deserialize, Serialize};
 use serde_json::to_string;
+use std::collections::HashMap;
 use wasm_bindgen::prelude::*;
 use yew::{
     format::{Json, Nothing},
@@ -24,6 +26,8 @@ pub struct ListPostsPage {
     list_link: WeakComponentLink<MatList>,
     page: i64,
     count: i64,
+    /// Summaries of the posts listed so far, by id.
+    summaries: HashMap<i64, RenderedPost>,
 }
 
 pub enum Msg {
@@ -31,6 +35,7 @@ pub enum Msg {
     GetCounts,
     ReceivePostHeadersResponse(FetchState<ResponseBlock<PostsResponse>>),
     ReceiveCountsResponse(FetchState<ResponseBlock<CountPostsResponse>>),
+    ReceivePost(i64, FetchState<ResponseBlock<ViewPostResponse>>),
     NextPage,
     PreviousPage,
 }
@@ -50,6 +55,7 @@ impl Component for ListPostsPage {
             list_link: WeakComponentLink::default(),
             page: 0,
             count: 0,
+            summaries: HashMap::new(),
         }
     }
 
@@ -57,11 +63,9 @@ impl Component for ListPostsPage {
         match msg {
             Msg::GetCounts => {
                 let future = async move {
-                    match get_post_counts().await {
+                    match ApiClient::new().get_post_counts().await {
                         Ok(info) => Msg::ReceiveCountsResponse(FetchState::Success(info)),
-                        Err(_) => Msg::ReceiveCountsResponse(FetchState::Failed(FetchError::from(
-                            JsValue::FALSE,
-                        ))),
+                        Err(e) => Msg::ReceiveCountsResponse(FetchState::Failed(e)),
                     }
                 };
                 send_future(self.link.clone(), future);
@@ -69,12 +73,11 @@ impl Component for ListPostsPage {
             }
             Msg::GetPostHeaders => {
                 let page = self.page;
+                let per_page = config::get().posts_per_page;
                 let future = async move {
-                    match posts(page * MAX_LIST_POSTS, MAX_LIST_POSTS).await {
+                    match ApiClient::new().posts(page * per_page, per_page).await {
                         Ok(info) => Msg::ReceivePostHeadersResponse(FetchState::Success(info)),
-                        Err(_) => Msg::ReceivePostHeadersResponse(FetchState::Failed(
-                            FetchError::from(JsValue::FALSE),
-                        )), // TODO
+                        Err(e) => Msg::ReceivePostHeadersResponse(FetchState::Failed(e)),
                     }
                 };
                 send_future(self.link.clone(), future);
@@ -90,11 +93,39 @@ impl Component for ListPostsPage {
                 true
             }
             Msg::ReceivePostHeadersResponse(data) => {
+                if let FetchState::Success(r) = &data {
+                    let headers = r.body.as_ref().map_or(&[][..], |body| &body.posts[..]);
+                    for id in headers.iter().map(|header| header.id as i64) {
+                        if self.summaries.contains_key(&id) {
+                            continue;
+                        }
+                        let future = async move {
+                            match ApiClient::new().view_post(id).await {
+                                Ok(info) => Msg::ReceivePost(id, FetchState::Success(info)),
+                                Err(e) => Msg::ReceivePost(id, FetchState::Failed(e)),
+                            }
+                        };
+                        send_future(self.link.clone(), future);
+                    }
+                }
                 self.fetch = data;
                 true
             }
+            Msg::ReceivePost(id, data) => {
+                let post = match data {
+                    FetchState::Success(r) => r.body.and_then(|body| body.post),
+                    _ => None,
+                };
+                match post {
+                    Some(post) => {
+                        self.summaries.insert(id, render::summarize(post.body));
+                        true
+                    }
+                    None => false,
+                }
+            }
             Msg::NextPage => {
-                if (self.page + 1) * MAX_LIST_POSTS < self.count {
+                if (self.page + 1) * config::get().posts_per_page < self.count {
                     self.page += 1;
                     self.fetch = FetchState::NotFetching;
                     true
@@ -135,6 +166,7 @@ impl Component for ListPostsPage {
         } else {
             vec![]
         };
+        let per_page = config::get().posts_per_page;
         html! {
             <div class="container">
             <div class="block">
@@ -142,15 +174,34 @@ impl Component for ListPostsPage {
                 <MatList list_link=self.list_link.clone()>
                     {
                         for list.iter().map(|post_header| {
+                            let summary = self
+                                .summaries
+                                .get(&(post_header.id as i64))
+                                .map(|summary| {
+                                    let front_matter = &summary.front_matter;
+                                    let mut labels = vec![];
+                                    if front_matter.draft {
+                                        labels.push(String::from("Draft"));
+                                    }
+                                    labels.extend(front_matter.series.clone());
+                                    labels.push(summary.reading_time());
+                                    format!("{} — {}", labels.join(" · "), summary.excerpt)
+                                })
+                                .unwrap_or_default();
                             html! {
-                                <router::MainRouterAnchor route=router::MainRoute::ViewPost(post_header.id as i64)><MatListItem>{&post_header.title}</MatListItem></router::MainRouterAnchor>
+                                <router::MainRouterAnchor route=router::MainRoute::ViewPost(post_header.id as i64)>
+                                    <MatListItem twoline=true>
+                                        <span>{&post_header.title}</span>
+                                        <span slot="secondary">{summary}</span>
+                                    </MatListItem>
+                                </router::MainRouterAnchor>
                             }
                         })
                     }
                 </MatList>
                 <div class="button-grid">
                     <span onclick=self.link.callback(|_| Msg::PreviousPage)><MatButton disabled=(self.page == 0) label="Previous"/></span>
-                    <span onclick=self.link.callback(|_| Msg::NextPage)><MatButton disabled=((self.page+1)*MAX_LIST_POSTS >= self.count) label="Next"/></span>
+                    <span onclick=self.link.callback(|_| Msg::NextPage)><MatButton disabled=((self.page+1)*per_page >= self.count) label="Next"/></span>
                 </div>
                 </div>
             </div>
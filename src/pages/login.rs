@@ -1,37 +1,41 @@
 use crate::api::*;
-use crate::services::cookie::CookieService;
+use crate::config;
 use crate::services::router;
+use crate::services::store::{self, AppState, AppStore};
 use serde::{Deserialize, Serialize};
 use serde_json::to_string;
+use std::rc::Rc;
 use wasm_bindgen::prelude::*;
+use yew::agent::{Bridge, Bridged};
 use yew::{
     format::{Json, Nothing},
     prelude::*,
 };
 use yew_material::text_inputs::*;
-use yew_material::{MatButton, MatFormfield, MatSnackbar, MatTextField, WeakComponentLink};
+use yew_material::{
+    MatButton, MatCheckbox, MatFormfield, MatSnackbar, MatTextField, WeakComponentLink,
+};
 use yew_router::agent::RouteRequest;
 use yew_router::prelude::*;
 
 pub struct LoginPage {
     link: ComponentLink<Self>,
-    root_link: ComponentLink<crate::Root>,
+    store: Box<dyn Bridge<AppStore>>,
     username: String,
     password: String,
-    cookie: CookieService,
+    remember: bool,
     error_link: WeakComponentLink<MatSnackbar>,
-    error_msg: AccountError,
+    error: Option<ApiError>,
     fetch_login: FetchState<ResponseBlock<LoginResponse>>,
-    fetch_info: FetchState<ResponseBlock<InfoResponse>>,
 }
 
 pub enum Msg {
     UpdateUsername(InputData),
     UpdatePassword(InputData),
+    UpdateRemember(bool),
     GetLogin,
-    GetInfo,
     ReceiveLoginResponse(FetchState<ResponseBlock<LoginResponse>>),
-    ReceiveInfoResponse(FetchState<ResponseBlock<InfoResponse>>),
+    StoreChanged(Rc<AppState>),
     ShowError,
     GoMain,
     GoRegister,
@@ -45,21 +49,16 @@ impl Component for LoginPage {
     type Properties = Props;
 
     fn create(_props: Props, link: ComponentLink<Self>) -> Self {
-        let mut any = link.get_parent().unwrap();
-        while let Some(l) = any.get_parent() {
-            any = l;
-        }
-        let root_link: ComponentLink<crate::Root> = any.clone().downcast();
+        let store = AppStore::bridge(link.callback(Msg::StoreChanged));
         Self {
             link,
-            root_link,
+            store,
             username: String::new(),
             password: String::new(),
-            cookie: CookieService::new(),
+            remember: false,
             error_link: WeakComponentLink::default(),
-            error_msg: AccountError::Nothing,
+            error: None,
             fetch_login: FetchState::NotFetching,
-            fetch_info: FetchState::NotFetching,
         }
     }
 
@@ -73,50 +72,51 @@ impl Component for LoginPage {
                 self.password = s.value;
                 false
             }
+            Msg::UpdateRemember(remember) => {
+                self.remember = remember;
+                false
+            }
             Msg::GetLogin => {
                 let form = LoginForm {
                     username: self.username.clone(),
                     pass: self.password.clone(),
                 };
                 let future = async move {
-                    match login(form).await {
+                    match ApiClient::new().login(form).await {
                         Ok(info) => Msg::ReceiveLoginResponse(FetchState::Success(info)),
-                        Err(_) => Msg::ReceiveLoginResponse(FetchState::Failed(FetchError::from(
-                            JsValue::FALSE,
-                        ))),
+                        Err(e) => Msg::ReceiveLoginResponse(FetchState::Failed(e)),
                     }
                 };
                 send_future(self.link.clone(), future);
                 false
             }
-            Msg::GetInfo => {
-                let future = async move {
-                    match get_info().await {
-                        Ok(info) => Msg::ReceiveInfoResponse(FetchState::Success(info)),
-                        Err(_) => Msg::ReceiveInfoResponse(FetchState::Failed(FetchError::from(
-                            JsValue::FALSE,
-                        ))), // TODO
-                    }
-                };
-                send_future(self.link.clone(), future);
+            Msg::StoreChanged(state) => {
+                if state.is_logined() {
+                    self.link.send_message(Msg::GoMain);
+                }
                 false
             }
-            Msg::ReceiveInfoResponse(data) => {
-                self.fetch_info = data;
-                true
-            }
             Msg::ReceiveLoginResponse(data) => {
                 if let FetchState::Success(resp) = data.clone() {
                     if let Some(body) = resp.body {
                         match body.result {
-                            AccountError::Nothing => {}
+                            AccountError::Nothing => {
+                                if let Some(token) = body.token {
+                                    self.store.send(store::Request::Login {
+                                        token,
+                                        remember: self.remember,
+                                    });
+                                }
+                            }
                             _ => {
-                                self.error_msg = body.result;
+                                self.error = Some(body.result.into());
                             }
                         }
                     } else {
-                        self.error_msg = AccountError::NetworkError;
+                        self.error = Some(AccountError::NetworkError.into());
                     }
+                } else if let FetchState::Failed(e) = data.clone() {
+                    self.error = Some(e);
                 }
                 self.fetch_login = data;
                 true
@@ -146,33 +146,24 @@ impl Component for LoginPage {
     }
 
     fn view(&self) -> Html {
-        if let FetchState::Success(r) = self.fetch_info.clone() {
-            if r.status {
-                self.link.send_message(Msg::GoMain);
-            }
-        } else if let FetchState::NotFetching = self.fetch_info.clone() {
-            self.link.send_message(Msg::GetInfo);
-        }
         if let FetchState::Success(resp) = self.fetch_login.clone() {
             if let Some(body) = resp.body {
                 match body.result {
-                    AccountError::Nothing => {
-                        self.cookie.set("token", &body.token.unwrap());
-                        self.root_link.send_message(crate::Msg::GetInfo);
-                        self.link.send_message(Msg::GoMain);
-                    }
+                    AccountError::Nothing => {}
                     _ => self.link.send_message(Msg::ShowError),
                 }
             } else {
                 self.link.send_message(Msg::ShowError)
             }
+        } else if let FetchState::Failed(_) = self.fetch_login {
+            self.link.send_message(Msg::ShowError);
         }
         html! {
             <div class="container">
-                <MatSnackbar label_text=&format!("Failed to login: {}", self.error_msg) snackbar_link=self.error_link.clone()/>
+                <MatSnackbar label_text=&format!("Failed to login: {}", self.error.as_ref().map(ToString::to_string).unwrap_or_default()) snackbar_link=self.error_link.clone()/>
                 <div class="form">
                     <div class="field">
-                        <h3>{"Login to ANEP Research"}</h3>
+                        <h3>{format!("Login to {}", config::get().site_title)}</h3>
                     </div>
                     <div class="field">
                         <MatFormfield>
@@ -184,6 +175,11 @@ impl Component for LoginPage {
                             <MatTextField outlined=true icon="lock" label="Password" value=self.password.clone() field_type=TextFieldType::Password oninput=self.link.callback(|s| Msg::UpdatePassword(s))/>
                         </MatFormfield>
                     </div>
+                    <div class="field">
+                        <MatFormfield label="Remember me">
+                            <MatCheckbox checked=self.remember onchange=self.link.callback(|checked| Msg::UpdateRemember(checked))/>
+                        </MatFormfield>
+                    </div>
                     <div class="field">
                         {"Haven't an account? "}<router::MainRouterAnchor route=router::MainRoute::Register>{"click here"}</router::MainRouterAnchor>
                     </div>
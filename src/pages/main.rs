@@ -38,11 +38,9 @@ impl Component for Main {
             Msg::GetRecentPosts => {
                 self.fetch = FetchState::Fetching;
                 let future = async move {
-                    match recent_posts().await {
+                    match ApiClient::new().recent_posts().await {
                         Ok(info) => Msg::ReceiveRecentPosts(FetchState::Success(info)),
-                        Err(_) => Msg::ReceiveRecentPosts(FetchState::Failed(FetchError::from(
-                            JsValue::FALSE,
-                        ))),
+                        Err(e) => Msg::ReceiveRecentPosts(FetchState::Failed(e)),
                     }
                 };
                 send_future(self.link.clone(), future);
@@ -1,11 +1,16 @@
 use crate::api::*;
+use crate::config;
 use crate::constants::*;
 use crate::services::cookie::CookieService;
+use crate::services::render::{self, RenderedPost};
 use crate::services::router;
+use crate::services::store::{self, AppState, AppStore};
 use chrono::prelude::*;
-use chrono_tz::Asia::Seoul;
 use std::cmp::min;
+use std::rc::Rc;
 use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use yew::agent::{Bridge, Bridged};
 use yew::prelude::*;
 use yew_material::list::*;
 use yew_material::{MatButton, MatList};
@@ -15,6 +20,10 @@ pub struct PostPreview {
     link: ComponentLink<Self>,
     fetch: FetchState<ResponseBlock<ViewPostResponse>>,
     fetch_info: FetchState<ResponseBlock<InfoResponse>>,
+    store: Box<dyn Bridge<AppStore>>,
+    state: Rc<AppState>,
+    /// The body last summarized, and its summary.
+    summary: Option<(String, RenderedPost)>,
 }
 
 #[derive(Properties, Clone)]
@@ -27,6 +36,48 @@ pub enum Msg {
     GetInfo(i32),
     ReceiveViewResponse(FetchState<ResponseBlock<ViewPostResponse>>),
     ReceiveInfoResponse(FetchState<ResponseBlock<InfoResponse>>),
+    StoreChanged(Rc<AppState>),
+}
+
+/// The post a `.post-embed` block of a rendered post body embeds, for a `PostPreview` of it to
+/// be shown in its place.
+pub fn embedded_post(node: &web_sys::Node) -> Option<i64> {
+    let embed = node.dyn_ref::<web_sys::Element>()?;
+    if !embed.matches(".post-embed").unwrap_or(false) {
+        return None;
+    }
+    let href = embed
+        .query_selector("a.post-link")
+        .ok()??
+        .get_attribute("href")?;
+    match router::route_of(&href)? {
+        router::MainRoute::ViewPost(id) => Some(id),
+        _ => None,
+    }
+}
+
+impl PostPreview {
+    /// Summarizes the fetched or cached post body if it is new or changed.
+    fn update_summary(&mut self) {
+        let body = match &self.fetch {
+            FetchState::Success(resp) => resp
+                .body
+                .as_ref()
+                .and_then(|body| body.post.as_ref())
+                .map(|post| post.body.clone()),
+            _ => self
+                .state
+                .posts
+                .get(&self.props.id)
+                .map(|post| post.body.clone()),
+        };
+        if let Some(body) = body {
+            if self.summary.as_ref().map(|(source, _)| source) != Some(&body) {
+                let summary = render::summarize(body.clone());
+                self.summary = Some((body, summary));
+            }
+        }
+    }
 }
 
 impl Component for PostPreview {
@@ -34,11 +85,15 @@ impl Component for PostPreview {
     type Message = Msg;
 
     fn create(props: Props, link: ComponentLink<Self>) -> Self {
+        let store = AppStore::bridge(link.callback(Msg::StoreChanged));
         Self {
             props,
             link,
             fetch: FetchState::NotFetching,
             fetch_info: FetchState::NotFetching,
+            store,
+            state: Rc::new(AppState::default()),
+            summary: None,
         }
     }
 
@@ -48,27 +103,30 @@ impl Component for PostPreview {
                 let id = self.props.id;
                 self.fetch = FetchState::Fetching;
                 let future = async move {
-                    match view_post(id).await {
+                    match ApiClient::new().view_post(id).await {
                         Ok(info) => Msg::ReceiveViewResponse(FetchState::Success(info)),
-                        Err(_) => Msg::ReceiveViewResponse(FetchState::Failed(FetchError::from(
-                            JsValue::FALSE,
-                        ))),
+                        Err(e) => Msg::ReceiveViewResponse(FetchState::Failed(e)),
                     }
                 };
                 send_future(self.link.clone(), future);
                 false
             }
             Msg::ReceiveViewResponse(info) => {
+                if let FetchState::Success(resp) = &info {
+                    if let Some(post) = resp.body.as_ref().and_then(|body| body.post.clone()) {
+                        self.store
+                            .send(store::Request::CachePost(self.props.id, post));
+                    }
+                }
                 self.fetch = info;
+                self.update_summary();
                 true
             }
             Msg::GetInfo(user) => {
                 let future = async move {
-                    match get_info_by_pk(user).await {
+                    match ApiClient::new().get_info_by_pk(user).await {
                         Ok(info) => Msg::ReceiveInfoResponse(FetchState::Success(info)),
-                        Err(_) => Msg::ReceiveInfoResponse(FetchState::Failed(FetchError::from(
-                            JsValue::FALSE,
-                        ))), // TODO
+                        Err(e) => Msg::ReceiveInfoResponse(FetchState::Failed(e)),
                     }
                 };
                 send_future(self.link.clone(), future);
@@ -78,11 +136,24 @@ impl Component for PostPreview {
                 self.fetch_info = info;
                 true
             }
+            Msg::StoreChanged(state) => {
+                self.state = state;
+                self.update_summary();
+                true
+            }
         }
     }
 
-    fn change(&mut self, _props: Props) -> ShouldRender {
-        false
+    fn change(&mut self, props: Props) -> ShouldRender {
+        if props.id == self.props.id {
+            return false;
+        }
+        self.props = props;
+        self.fetch = FetchState::NotFetching;
+        self.fetch_info = FetchState::NotFetching;
+        self.summary = None;
+        self.update_summary();
+        true
     }
 
     fn view(&self) -> Html {
@@ -92,7 +163,13 @@ impl Component for PostPreview {
         let info = if let FetchState::Success(resp) = self.fetch.clone() {
             resp.body
         } else {
-            None
+            self.state
+                .posts
+                .get(&self.props.id)
+                .map(|post| ViewPostResponse {
+                    error: BlogError::Nothing,
+                    post: Some(post.clone()),
+                })
         };
         let user = if let Some(resp) = info.clone() {
             if let Some(post) = resp.post {
@@ -122,15 +199,53 @@ impl Component for PostPreview {
                 if tags == String::from("#") {
                     tags = String::from("NO TAGS");
                 }
+                let front_matter = self
+                    .summary
+                    .as_ref()
+                    .map(|(_, summary)| summary.front_matter.clone())
+                    .unwrap_or_default();
                 html! {
                 <>
-                    <h2>{post.title}</h2>
+                    {
+                        if let Some(cover) = front_matter.cover_url() {
+                            html! { <img class="cover" src=cover.to_string() alt=""/> }
+                        } else {
+                            html! {}
+                        }
+                    }
+                    <h2>
+                        {post.title}
+                        {if front_matter.draft { html! { <span class="draft-badge">{"Draft"}</span> } } else { html! {} }}
+                    </h2>
+                    {
+                        if let Some(series) = &front_matter.series {
+                            html! { <p class="series">{format!("Series: {}", series)}</p> }
+                        } else {
+                            html! {}
+                        }
+                    }
                     <MatList><li divider=true role="separator"></li></MatList>
                     <MatListItem graphic=GraphicType::Avatar twoline=true noninteractive=true>
                         <span>{if let Some(user_info) = user { user_info.nickname.clone() } else { String::from("ERROR") }}</span>
-                        <span slot="secondary">{&format!("{}", Seoul.from_utc_datetime(&post.created_at))}</span>
+                        <span slot="secondary">{&format!("{}", config::get().timezone().from_utc_datetime(&post.created_at))}</span>
                         <mwc-icon slot="graphic" class="inverted">{"tag_faces"}</mwc-icon>
                     </MatListItem>
+                    {
+                        if let Some((_, summary)) = &self.summary {
+                            let excerpt = match &front_matter.lang {
+                                Some(lang) => html! { <p class="excerpt" lang=lang.clone()>{&summary.excerpt}</p> },
+                                None => html! { <p class="excerpt">{&summary.excerpt}</p> },
+                            };
+                            html! {
+                                <>
+                                    {excerpt}
+                                    <p class="reading-time">{summary.reading_time()}</p>
+                                </>
+                            }
+                        } else {
+                            html! {}
+                        }
+                    }
                     <MatList><li divider=true role="separator"></li></MatList>
                     <MatListItem noninteractive=true><b>{tags}</b></MatListItem>
                     <router::MainRouterAnchor route=router::MainRoute::ViewPost(self.props.id)><MatButton label="Read more" raised=true/></router::MainRouterAnchor>
@@ -1,4 +1,5 @@
 use crate::api::*;
+use crate::config;
 use crate::services::cookie::CookieService;
 use crate::services::router;
 use serde::{Deserialize, Serialize};
@@ -21,7 +22,7 @@ pub struct RegisterPage {
     email: String,
     nickname: String,
     error_link: WeakComponentLink<MatSnackbar>,
-    error_msg: AccountError,
+    error: Option<ApiError>,
     fetch_register: FetchState<ResponseBlock<RegisterResponse>>,
 }
 
@@ -53,7 +54,7 @@ impl Component for RegisterPage {
             email: String::new(),
             nickname: String::new(),
             error_link: WeakComponentLink::default(),
-            error_msg: AccountError::Nothing,
+            error: None,
             fetch_register: FetchState::NotFetching,
         }
     }
@@ -82,7 +83,7 @@ impl Component for RegisterPage {
             }
             Msg::GetRegister => {
                 if self.password.clone() != self.password_verify.clone() {
-                    self.error_msg = AccountError::PasswordVerifyFailed;
+                    self.error = Some(AccountError::PasswordVerifyFailed.into());
                     true
                 } else {
                     let form = RegisterForm {
@@ -92,11 +93,9 @@ impl Component for RegisterPage {
                         pass: self.password.clone(),
                     };
                     let future = async move {
-                        match register(form).await {
+                        match ApiClient::new().register(form).await {
                             Ok(info) => Msg::ReceiveRegisterResponse(FetchState::Success(info)),
-                            Err(_) => Msg::ReceiveRegisterResponse(FetchState::Failed(
-                                FetchError::from(JsValue::FALSE),
-                            )),
+                            Err(e) => Msg::ReceiveRegisterResponse(FetchState::Failed(e)),
                         }
                     };
                     send_future(self.link.clone(), future);
@@ -109,12 +108,14 @@ impl Component for RegisterPage {
                         match body.result {
                             AccountError::Nothing => {}
                             _ => {
-                                self.error_msg = body.result;
+                                self.error = Some(body.result.into());
                             }
                         }
                     } else {
-                        self.error_msg = AccountError::NetworkError;
+                        self.error = Some(AccountError::NetworkError.into());
                     }
+                } else if let FetchState::Failed(e) = data.clone() {
+                    self.error = Some(e);
                 }
                 self.fetch_register = data;
                 true
@@ -150,7 +151,7 @@ impl Component for RegisterPage {
             }
             state
         });
-        if AccountError::PasswordVerifyFailed == self.error_msg {
+        if self.error == Some(AccountError::PasswordVerifyFailed.into()) {
             self.link.send_message(Msg::ShowError);
         }
         if let FetchState::Success(resp) = self.fetch_register.clone() {
@@ -166,13 +167,15 @@ impl Component for RegisterPage {
             } else {
                 self.link.send_message(Msg::ShowError);
             }
+        } else if let FetchState::Failed(_) = self.fetch_register {
+            self.link.send_message(Msg::ShowError);
         }
         html! {
             <div class="container">
-                <MatSnackbar label_text=&format!("Failed to register: {}", self.error_msg) snackbar_link=self.error_link.clone()/>
+                <MatSnackbar label_text=&format!("Failed to register: {}", self.error.as_ref().map(ToString::to_string).unwrap_or_default()) snackbar_link=self.error_link.clone()/>
                 <div class="form">
                     <div class="field">
-                        <h3>{"Register to ANEP Research"}</h3>
+                        <h3>{format!("Register to {}", config::get().site_title)}</h3>
                     </div>
                     <div class="field">
                         <MatFormfield>
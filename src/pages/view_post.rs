@@ -1,14 +1,17 @@
 use crate::api::*;
+use crate::config;
 use crate::constants::*;
+use crate::pages::post_preview;
 use crate::services::cookie::CookieService;
-use crate::services::render;
+use crate::services::render::{self, Heading, RenderedPost};
 use crate::services::router;
+use crate::services::store::{self, AppState, AppStore};
 use chrono::prelude::*;
-use chrono_tz::Asia::Seoul;
 use std::cmp::min;
-use std::convert::TryFrom;
+use std::rc::Rc;
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
+use yew::agent::{Bridge, Bridged};
 use yew::prelude::*;
 use yew::virtual_dom::VNode;
 use yew_material::dialog::*;
@@ -17,16 +20,42 @@ use yew_material::{MatButton, MatList, MatSnackbar, WeakComponentLink};
 use yew_router::agent::RouteRequest;
 use yew_router::prelude::*;
 
+/// Pixels below the top of the scrolling area at which a heading counts as reached.
+const SECTION_OFFSET: f64 = 16.0;
+
+/// A post body rendered once, so re-rendering the page for the table of contents does not
+/// rebuild it.
+struct Rendered {
+    source: String,
+    pieces: Vec<Piece>,
+    post: RenderedPost,
+}
+
+/// A top-level block of a rendered post body: markup shown as it is, or a post embedded with
+/// `![[post:ID]]`, whose preview is a component of the page. Embeds within other blocks stay
+/// links to the post.
+enum Piece {
+    Markup(web_sys::Node),
+    Embed(i64),
+}
+
 pub struct ViewPost {
     props: Props,
     link: ComponentLink<Self>,
     error_link: WeakComponentLink<MatSnackbar>,
-    error_msg: BlogError,
+    error: Option<ApiError>,
     fetch: FetchState<ResponseBlock<ViewPostResponse>>,
     fetch_info: FetchState<ResponseBlock<InfoResponse>>,
-    fetch_info2: FetchState<ResponseBlock<InfoResponse>>,
+    store: Box<dyn Bridge<AppStore>>,
+    state: Rc<AppState>,
     fetch_delete: FetchState<ResponseBlock<DeletePostResponse>>,
     delete_dialog: WeakComponentLink<MatDialog>,
+    rendered: Option<Rendered>,
+    /// Id of the heading of the section being read.
+    section: Option<String>,
+    /// Set when a body is rendered, to scroll to the section in the URL once it is shown.
+    scroll_to_hash: bool,
+    scroll_listener: Option<(web_sys::EventTarget, Closure<dyn FnMut()>)>,
 }
 
 #[derive(Properties, Clone)]
@@ -36,34 +65,140 @@ pub struct Props {
 
 pub enum Msg {
     GoMain,
+    /// Opens another post, such as one linked with `[[post:ID]]`.
+    GoPost(i64),
     GetPreview,
     GetInfo(i32),
-    GetInfo2,
     GetDelete,
     ShowError,
     ReceiveViewResponse(FetchState<ResponseBlock<ViewPostResponse>>),
     ReceiveInfoResponse(FetchState<ResponseBlock<InfoResponse>>),
-    ReceiveInfo2Response(FetchState<ResponseBlock<InfoResponse>>),
+    StoreChanged(Rc<AppState>),
     ReceiveDeletePostResponse(FetchState<ResponseBlock<DeletePostResponse>>),
     ShowDeleteDialog,
+    /// The page scrolled; the highlighted section may change.
+    Scrolled,
+    /// Scrolls to the heading with this id.
+    ScrollTo(String),
     Dummy,
 }
 
+impl ViewPost {
+    /// The fetched post, or the one cached in the store until the fetch is done.
+    fn post(&self) -> Option<PublicPost> {
+        match &self.fetch {
+            FetchState::Success(resp) => resp.body.as_ref()?.post.clone(),
+            _ => self.state.posts.get(&self.props.id).cloned(),
+        }
+    }
+
+    /// Renders the post body if it is new or changed.
+    fn update_rendered(&mut self) {
+        let body = match self.post() {
+            Some(post) => post.body,
+            None => return,
+        };
+        if self.rendered.as_ref().map(|rendered| &rendered.source) == Some(&body) {
+            return;
+        }
+        let post = render::render(body.clone());
+        let element = yew::utils::document().create_element("div").unwrap();
+        element.set_inner_html(&post.html);
+        let nodes = element.child_nodes();
+        let pieces = (0..nodes.length())
+            .filter_map(|i| nodes.item(i))
+            .map(|node| match post_preview::embedded_post(&node) {
+                Some(id) => Piece::Embed(id),
+                None => Piece::Markup(node),
+            })
+            .collect();
+        self.scroll_to_hash = self.rendered.is_none();
+        self.rendered = Some(Rendered {
+            source: body,
+            pieces,
+            post,
+        });
+    }
+
+    /// The last heading scrolled past the top of the scrolling area.
+    fn current_section(&self) -> Option<String> {
+        let document = yew::utils::document();
+        let top = document
+            .get_element_by_id("router-outlet")
+            .map_or(0.0, |outlet| outlet.get_bounding_client_rect().top())
+            + SECTION_OFFSET;
+        let mut section = None;
+        for heading in &self.rendered.as_ref()?.post.toc {
+            match document.get_element_by_id(&heading.id) {
+                Some(element) if element.get_bounding_client_rect().top() > top => break,
+                Some(_) => section = Some(heading.id.clone()),
+                None => {}
+            }
+        }
+        section
+    }
+
+    fn view_body(&self, rendered: &Rendered) -> Html {
+        let pieces = rendered.pieces.iter().map(|piece| match piece {
+            Piece::Markup(node) => VNode::VRef(node.clone()),
+            Piece::Embed(id) => {
+                let id = *id;
+                html! { <div class="post-embed"><post_preview::PostPreview id=id/></div> }
+            }
+        });
+        match &rendered.post.front_matter.lang {
+            Some(lang) => html! { <div lang=lang.clone()>{ for pieces }</div> },
+            None => html! { <div>{ for pieces }</div> },
+        }
+    }
+
+    fn view_toc(&self, toc: &[Heading]) -> Html {
+        if toc.is_empty() {
+            return html! {};
+        }
+        let top_level = toc.iter().map(|heading| heading.level).min().unwrap_or(1);
+        html! {
+            <nav class="toc">
+                <b>{"Contents"}</b>
+                <ul>
+                {
+                    for toc.iter().map(|heading| {
+                        let id = heading.id.clone();
+                        let class = if self.section.as_ref() == Some(&heading.id) { "active" } else { "" };
+                        html! {
+                            <li class=class style=format!("margin-left: {}em", heading.level - top_level)>
+                                <a href=format!("#{}", heading.id) onclick=self.link.callback(move |e: MouseEvent| { e.prevent_default(); Msg::ScrollTo(id.clone()) })>{&heading.title}</a>
+                            </li>
+                        }
+                    })
+                }
+                </ul>
+            </nav>
+        }
+    }
+}
+
 impl Component for ViewPost {
     type Properties = Props;
     type Message = Msg;
 
     fn create(props: Props, link: ComponentLink<Self>) -> Self {
+        let store = AppStore::bridge(link.callback(Msg::StoreChanged));
         Self {
             props,
             link,
             error_link: WeakComponentLink::default(),
-            error_msg: BlogError::Nothing,
+            error: None,
             fetch: FetchState::NotFetching,
             fetch_info: FetchState::NotFetching,
-            fetch_info2: FetchState::NotFetching,
+            store,
+            state: Rc::new(AppState::default()),
             fetch_delete: FetchState::NotFetching,
             delete_dialog: WeakComponentLink::default(),
+            rendered: None,
+            section: None,
+            scroll_to_hash: false,
+            scroll_listener: None,
         }
     }
 
@@ -73,39 +208,30 @@ impl Component for ViewPost {
                 let id = self.props.id;
                 self.fetch = FetchState::Fetching;
                 let future = async move {
-                    match view_post(id).await {
+                    match ApiClient::new().view_post(id).await {
                         Ok(info) => Msg::ReceiveViewResponse(FetchState::Success(info)),
-                        Err(_) => Msg::ReceiveViewResponse(FetchState::Failed(FetchError::from(
-                            JsValue::FALSE,
-                        ))),
+                        Err(e) => Msg::ReceiveViewResponse(FetchState::Failed(e)),
                     }
                 };
                 send_future(self.link.clone(), future);
                 false
             }
             Msg::ReceiveViewResponse(info) => {
+                if let FetchState::Success(resp) = &info {
+                    if let Some(post) = resp.body.as_ref().and_then(|body| body.post.clone()) {
+                        self.store
+                            .send(store::Request::CachePost(self.props.id, post));
+                    }
+                }
                 self.fetch = info;
+                self.update_rendered();
                 true
             }
             Msg::GetInfo(user) => {
                 let future = async move {
-                    match get_info_by_pk(user).await {
+                    match ApiClient::new().get_info_by_pk(user).await {
                         Ok(info) => Msg::ReceiveInfoResponse(FetchState::Success(info)),
-                        Err(_) => Msg::ReceiveInfoResponse(FetchState::Failed(FetchError::from(
-                            JsValue::FALSE,
-                        ))), // TODO
-                    }
-                };
-                send_future(self.link.clone(), future);
-                false
-            }
-            Msg::GetInfo2 => {
-                let future = async move {
-                    match get_info().await {
-                        Ok(info) => Msg::ReceiveInfo2Response(FetchState::Success(info)),
-                        Err(_) => Msg::ReceiveInfo2Response(FetchState::Failed(FetchError::from(
-                            JsValue::FALSE,
-                        ))), // TODO
+                        Err(e) => Msg::ReceiveInfoResponse(FetchState::Failed(e)),
                     }
                 };
                 send_future(self.link.clone(), future);
@@ -113,13 +239,11 @@ impl Component for ViewPost {
             }
             Msg::GetDelete => {
                 let id = self.props.id;
-                self.fetch = FetchState::Fetching;
+                self.fetch_delete = FetchState::Fetching;
                 let future = async move {
-                    match delete_post(id).await {
+                    match ApiClient::new().delete_post(id).await {
                         Ok(info) => Msg::ReceiveDeletePostResponse(FetchState::Success(info)),
-                        Err(_) => Msg::ReceiveDeletePostResponse(FetchState::Failed(
-                            FetchError::from(JsValue::FALSE),
-                        )),
+                        Err(e) => Msg::ReceiveDeletePostResponse(FetchState::Failed(e)),
                     }
                 };
                 send_future(self.link.clone(), future);
@@ -131,20 +255,38 @@ impl Component for ViewPost {
                 router.send(RouteRequest::ChangeRoute(route));
                 false
             }
+            Msg::GoPost(id) => {
+                let mut router = RouteAgentDispatcher::<()>::new();
+                let route = Route::from(router::MainRoute::ViewPost(id));
+                router.send(RouteRequest::ChangeRoute(route));
+                false
+            }
             Msg::ReceiveInfoResponse(info) => {
                 self.fetch_info = info;
                 true
             }
-            Msg::ReceiveInfo2Response(info2) => {
-                self.fetch_info2 = info2;
+            Msg::StoreChanged(state) => {
+                self.state = state;
+                self.update_rendered();
                 true
             }
             Msg::ReceiveDeletePostResponse(info) => {
                 self.fetch_delete = info.clone();
-                if let FetchState::Success(resp) = info {
-                    if let Some(body) = resp.body {
-                        self.error_msg = body.error;
+                match info {
+                    FetchState::Success(resp) => {
+                        if let Some(body) = resp.body {
+                            if body.error != BlogError::Nothing {
+                                self.error = Some(body.error.into());
+                            } else {
+                                self.store.send(store::Request::ForgetPost(self.props.id));
+                            }
+                        }
                     }
+                    FetchState::Failed(e) => {
+                        self.error = Some(e);
+                        self.link.send_message(Msg::ShowError);
+                    }
+                    _ => {}
                 }
                 true
             }
@@ -156,30 +298,107 @@ impl Component for ViewPost {
                 self.delete_dialog.show();
                 false
             }
+            Msg::Scrolled => {
+                let section = self.current_section();
+                if section != self.section {
+                    self.section = section;
+                    true
+                } else {
+                    false
+                }
+            }
+            Msg::ScrollTo(id) => {
+                let document = yew::utils::document();
+                // Links written by hand, and hashes typed in, may leave out the prefix
+                // rendered ids get.
+                let target = document.get_element_by_id(&id).or_else(|| {
+                    document.get_element_by_id(&format!("{}{}", render::ID_PREFIX, id))
+                });
+                let id = target.as_ref().map_or(id, |target| target.id());
+                if let Some(heading) = target {
+                    heading.scroll_into_view();
+                    if let Ok(history) = yew::utils::window().history() {
+                        let url = format!("#{}", id);
+                        history
+                            .replace_state_with_url(&JsValue::NULL, "", Some(&url))
+                            .ok();
+                    }
+                }
+                self.section = Some(id);
+                true
+            }
             _ => false,
         }
     }
 
-    fn change(&mut self, _props: Props) -> ShouldRender {
-        false
+    fn change(&mut self, props: Props) -> ShouldRender {
+        if props.id == self.props.id {
+            return false;
+        }
+        // Another post, opened from a link in this one: start over with it.
+        self.props = props;
+        self.fetch = FetchState::NotFetching;
+        self.fetch_info = FetchState::NotFetching;
+        self.fetch_delete = FetchState::NotFetching;
+        self.rendered = None;
+        self.section = None;
+        self.update_rendered();
+        true
+    }
+
+    fn rendered(&mut self, first_render: bool) {
+        if first_render {
+            // Pages scroll inside `#router-outlet`; the window is used when mounted elsewhere.
+            let target: web_sys::EventTarget = yew::utils::document()
+                .get_element_by_id("router-outlet")
+                .map(Into::into)
+                .unwrap_or_else(|| yew::utils::window().into());
+            let link = self.link.clone();
+            let listener = Closure::wrap(
+                Box::new(move || link.send_message(Msg::Scrolled)) as Box<dyn FnMut()>
+            );
+            target
+                .add_event_listener_with_callback("scroll", listener.as_ref().unchecked_ref())
+                .ok();
+            self.scroll_listener = Some((target, listener));
+        }
+        if self.scroll_to_hash && self.rendered.is_some() {
+            self.scroll_to_hash = false;
+            let hash = yew::utils::window().location().hash().unwrap_or_default();
+            if let Some(id) = hash.strip_prefix('#') {
+                if let Some(id) = js_sys::decode_uri_component(id)
+                    .ok()
+                    .and_then(|id| id.as_string())
+                {
+                    self.link.send_message(Msg::ScrollTo(id));
+                }
+            }
+        }
+    }
+
+    fn destroy(&mut self) {
+        if let Some((target, listener)) = self.scroll_listener.take() {
+            target
+                .remove_event_listener_with_callback("scroll", listener.as_ref().unchecked_ref())
+                .ok();
+        }
     }
 
     fn view(&self) -> Html {
         if let FetchState::NotFetching = self.fetch.clone() {
             self.link.send_message(Msg::GetPreview);
         }
-        let user_logined = if let FetchState::NotFetching = self.fetch_info2.clone() {
-            self.link.send_message(Msg::GetInfo2);
-            None
-        } else if let FetchState::Success(user) = self.fetch_info2.clone() {
-            user.body
-        } else {
-            None
-        };
+        let user_logined = self.state.user.clone();
         let info = if let FetchState::Success(resp) = self.fetch.clone() {
             resp.body
         } else {
-            None
+            self.state
+                .posts
+                .get(&self.props.id)
+                .map(|post| ViewPostResponse {
+                    error: BlogError::Nothing,
+                    post: Some(post.clone()),
+                })
         };
         let user = if let Some(resp) = info.clone() {
             if let Some(post) = resp.post {
@@ -211,7 +430,6 @@ impl Component for ViewPost {
         }
         if let Some(resp) = info {
             if let Some(post) = resp.post {
-                let html_output = render::render(post.body);
                 let mut tags = String::new();
                 for i in 0..post.tags.len() {
                     tags.push_str(&format!("#{}", post.tags[i].to_uppercase()));
@@ -222,17 +440,46 @@ impl Component for ViewPost {
                 if tags == String::from("#") {
                     tags = String::from("NO TAGS");
                 }
-                let window = web_sys::window().unwrap();
-                let document = window.document().unwrap();
-                let html_document = document.dyn_into::<web_sys::HtmlDocument>().unwrap();
-                let render: web_sys::HtmlDivElement = html_document
-                    .create_element("div")
-                    .unwrap()
-                    .dyn_into::<web_sys::HtmlDivElement>()
-                    .unwrap();
-                render.set_inner_html(&html_output);
+                let (body, toc, reading_time) = match &self.rendered {
+                    Some(rendered) => (
+                        self.view_body(rendered),
+                        self.view_toc(&rendered.post.toc),
+                        format!(" · {}", rendered.post.reading_time()),
+                    ),
+                    None => (html! {}, html! {}, String::new()),
+                };
+                let front_matter = self
+                    .rendered
+                    .as_ref()
+                    .map(|rendered| rendered.post.front_matter.clone())
+                    .unwrap_or_default();
+                // Links to `#id` inside the post scroll to it instead of going through the router,
+                // and links to other posts go through the router instead of reloading the page.
+                let onclick = self.link.callback(|e: MouseEvent| {
+                    let href = e
+                        .target()
+                        .and_then(|target| target.dyn_into::<web_sys::Element>().ok())
+                        .and_then(|element| {
+                            element.closest("a[href^='#'], a.post-link").ok().flatten()
+                        })
+                        .and_then(|anchor| anchor.get_attribute("href"));
+                    match href {
+                        Some(href) if href.starts_with('#') => {
+                            e.prevent_default();
+                            Msg::ScrollTo(href[1..].to_string())
+                        }
+                        Some(href) => match router::route_of(&href) {
+                            Some(router::MainRoute::ViewPost(id)) => {
+                                e.prevent_default();
+                                Msg::GoPost(id)
+                            }
+                            _ => Msg::Dummy,
+                        },
+                        None => Msg::Dummy,
+                    }
+                });
                 html! {
-                    <div class="container">
+                    <div class="container post-layout">
                     <MatDialog heading="Are you sure?" dialog_link=self.delete_dialog.clone() onclosing=self.link.callback(|action: String| match action.as_str() { "yes" => Msg::GetDelete, _ => Msg::Dummy })>
                     {"Are you sure to delete this post?"}
                     <MatDialogAction action_type=ActionType::Secondary action="no">
@@ -242,25 +489,35 @@ impl Component for ViewPost {
                         <MatButton label="Yes" />
                     </MatDialogAction>
                 </MatDialog>
-                        <MatSnackbar label_text=&format!("Failed to create post: {}", self.error_msg) snackbar_link=self.error_link.clone()/>
+                        <MatSnackbar label_text=&format!("Failed to create post: {}", self.error.as_ref().map(ToString::to_string).unwrap_or_default()) snackbar_link=self.error_link.clone()/>
                         <div class="block">
-                        <h2>{post.title}</h2>
+                        {
+                            if let Some(cover) = front_matter.cover_url() {
+                                html! { <img class="cover" src=cover.to_string() alt=""/> }
+                            } else {
+                                html! {}
+                            }
+                        }
+                        <h2>
+                            {post.title}
+                            {if front_matter.draft { html! { <span class="draft-badge">{"Draft"}</span> } } else { html! {} }}
+                        </h2>
+                        {
+                            if let Some(series) = &front_matter.series {
+                                html! { <p class="series">{format!("Series: {}", series)}</p> }
+                            } else {
+                                html! {}
+                            }
+                        }
                         <MatList><li divider=true role="separator"></li></MatList>
                         <MatListItem graphic=GraphicType::Avatar twoline=true noninteractive=true>
                             <span>{if let Some(user_info) = user.clone() { user_info.nickname.clone() } else { String::from("ERROR") }}</span>
-                            <span slot="secondary">{&format!("{}", Seoul.from_utc_datetime(&post.created_at))}</span>
+                            <span slot="secondary">{&format!("{}{}", config::get().timezone().from_utc_datetime(&post.created_at), reading_time)}</span>
                             <mwc-icon slot="graphic" class="inverted">{"tag_faces"}</mwc-icon>
                         </MatListItem>
                         <MatList><li divider=true role="separator"></li></MatList>
-                            <div class="markdown-body">
-                        {if let Ok(node) = web_sys::Node::try_from(render) {
-                            let vnode = VNode::VRef(node);
-                            vnode
-                          } else {
-                            html! {
-                              <div class="error">{"error"}</div>
-                            }
-                          }}
+                            <div class="markdown-body" onclick=onclick>
+                                {body}
                             </div>
                         <MatList><li divider=true role="separator"></li></MatList>
                         <MatListItem noninteractive=true><b>{tags}</b></MatListItem>
@@ -285,10 +542,25 @@ impl Component for ViewPost {
                             }
                         }
                     </div>
+                    {toc}
                     </div>
                     }
             } else {
-                html! {}
+                let error = match resp.error {
+                    BlogError::Nothing => ApiError::Status(404),
+                    e => e.into(),
+                };
+                html! {
+                    <div class="container">
+                        <h2>{error.to_string()}</h2>
+                    </div>
+                }
+            }
+        } else if let FetchState::Failed(e) = self.fetch.clone() {
+            html! {
+                <div class="container">
+                    <h2>{e.to_string()}</h2>
+                </div>
             }
         } else {
             html! {}
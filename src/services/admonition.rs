@@ -0,0 +1,339 @@
+use super::render::{code_ranges, offset_in, SourceMap, ID_PREFIX};
+use super::sanitize::mark;
+use pulldown_cmark::escape::escape_html;
+use pulldown_cmark::Options;
+use std::collections::HashMap;
+use std::ops::Range;
+
+/// A kind of block set apart from the text around it.
+pub struct Kind {
+    /// Written after `:::` or in `> [!..]`, in any case.
+    pub name: &'static str,
+    /// Shown at the top of the block, before its number and title.
+    pub title: &'static str,
+    /// Whether blocks of this kind are numbered, each kind on its own, and can be referred to
+    /// with `[[ref:label]]`.
+    pub numbered: bool,
+    /// A callout is an `<aside role="note">` with an icon; other blocks are `<section>`s.
+    pub callout: bool,
+}
+
+impl Kind {
+    const fn callout(name: &'static str, title: &'static str) -> Self {
+        Self {
+            name,
+            title,
+            numbered: false,
+            callout: true,
+        }
+    }
+
+    const fn theorem(name: &'static str, title: &'static str, numbered: bool) -> Self {
+        Self {
+            name,
+            title,
+            numbered,
+            callout: false,
+        }
+    }
+
+    fn element(&self) -> &'static str {
+        if self.callout {
+            "aside"
+        } else {
+            "section"
+        }
+    }
+}
+
+/// GitHub's alerts, then theorem-like blocks.
+pub const KINDS: &[Kind] = &[
+    Kind::callout("note", "Note"),
+    Kind::callout("tip", "Tip"),
+    Kind::callout("important", "Important"),
+    Kind::callout("warning", "Warning"),
+    Kind::callout("caution", "Caution"),
+    Kind::theorem("theorem", "Theorem", true),
+    Kind::theorem("lemma", "Lemma", true),
+    Kind::theorem("corollary", "Corollary", true),
+    Kind::theorem("definition", "Definition", true),
+    Kind::theorem("example", "Example", true),
+    Kind::theorem("proof", "Proof", false),
+];
+
+/// What follows `:::` or `> [!KIND]`: a kind, then optionally `{#label}` and a title.
+struct Header<'a> {
+    kind: &'static Kind,
+    label: Option<&'a str>,
+    title: Option<&'a str>,
+}
+
+fn parse_header(header: &str) -> Result<Header<'_>, String> {
+    let header = header.trim();
+    let end = header
+        .find(|c: char| !c.is_ascii_alphabetic())
+        .unwrap_or(header.len());
+    let name = &header[..end];
+    let kind = KINDS
+        .iter()
+        .find(|kind| kind.name.eq_ignore_ascii_case(name))
+        .ok_or_else(|| {
+            let names: Vec<&str> = KINDS.iter().map(|kind| kind.name).collect();
+            format!(
+                "Unknown block {:?}; blocks are {}",
+                header,
+                names.join(", ")
+            )
+        })?;
+    let mut rest = header[end..].trim_start();
+    let mut label = None;
+    if let Some(after) = rest.strip_prefix("{#") {
+        let close = after
+            .find('}')
+            .ok_or_else(|| format!("The label of {:?} is missing its }}", header))?;
+        let name = after[..close].trim();
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err(format!(
+                "Label {:?} may only have letters, digits, - and _",
+                name
+            ));
+        }
+        label = Some(name);
+        rest = after[close + 1..].trim();
+    }
+    Ok(Header {
+        kind,
+        label,
+        title: Some(rest).filter(|title| !title.is_empty()),
+    })
+}
+
+/// What is after the `:::` (three or more colons) starting `line`, indented by at most three
+/// spaces.
+fn fence(line: &str) -> Option<&str> {
+    let indent = line.len() - line.trim_start_matches(' ').len();
+    let line = &line[indent..];
+    if indent > 3 || !line.starts_with(":::") {
+        return None;
+    }
+    Some(line.trim_start_matches(':'))
+}
+
+/// `line` without the `>` quoting it, and the one space after that, if it is quoted.
+fn unquote(line: &str) -> Option<&str> {
+    let indent = line.len() - line.trim_start_matches(' ').len();
+    if indent > 3 {
+        return None;
+    }
+    let rest = line[indent..].strip_prefix('>')?;
+    Some(rest.strip_prefix(' ').unwrap_or(rest))
+}
+
+/// The kind and the rest of a `> [!KIND]` line, which starts a GitHub-style alert.
+fn alert(line: &str) -> Option<&str> {
+    let rest = unquote(line)?.trim_start().strip_prefix("[!")?;
+    let close = rest.find(']')?;
+    let kind = &rest[..close];
+    if kind.is_empty() || !kind.chars().all(|c| c.is_ascii_alphabetic()) {
+        return None;
+    }
+    Some(rest)
+}
+
+/// Lines of `input` and the offsets they start at, line endings included.
+fn lines(input: &str) -> Vec<(usize, &str)> {
+    let mut lines = vec![];
+    let mut start = 0;
+    while start < input.len() {
+        let end = input[start..]
+            .find('\n')
+            .map_or(input.len(), |i| start + i + 1);
+        lines.push((start, &input[start..end]));
+        start = end;
+    }
+    lines
+}
+
+/// Numbers and labels given out so far, shared by nested blocks.
+struct State<'w> {
+    numbers: HashMap<&'static str, usize>,
+    labels: HashMap<String, String>,
+    warnings: &'w mut Vec<String>,
+}
+
+impl State<'_> {
+    /// The opening tag and title of a block. Numbered blocks get the next number of their
+    /// kind and an id to refer to them by: the label if there is one, or else `kind-number`,
+    /// after `ID_PREFIX`.
+    fn open(&mut self, header: &Header) -> String {
+        let kind = header.kind;
+        let mut heading = String::from(kind.title);
+        let mut id = header.label.map(str::to_string);
+        if kind.numbered {
+            let number = self.numbers.entry(kind.name).or_insert(0);
+            *number += 1;
+            heading = format!("{} {}", kind.title, number);
+            let id = id.get_or_insert_with(|| format!("{}-{}", kind.name, number));
+            if self.labels.contains_key(id.as_str()) {
+                self.warnings
+                    .push(format!("Label {:?} is given to more than one block", id));
+            } else {
+                self.labels.insert(id.clone(), heading.clone());
+            }
+        } else if let Some(label) = header.label {
+            self.warnings.push(format!(
+                "Label {:?} is on a {} block, which cannot be referred to",
+                label, kind.name
+            ));
+            id = None;
+        }
+        let mut output = format!(
+            "\n<{} class=\"admonition admonition-{}\"",
+            kind.element(),
+            kind.name
+        );
+        if kind.callout {
+            output.push_str(" role=\"note\"");
+        }
+        if let Some(id) = id {
+            output.push_str(" id=\"");
+            output.push_str(ID_PREFIX);
+            escape_html(&mut output, &id).unwrap();
+            output.push('"');
+        }
+        output.push_str("><p class=\"admonition-title\">");
+        match (kind.numbered, header.title) {
+            (true, Some(title)) => {
+                escape_html(&mut output, &heading).unwrap();
+                output.push_str(" (");
+                escape_html(&mut output, title).unwrap();
+                output.push(')');
+            }
+            (false, Some(title)) => escape_html(&mut output, title).unwrap(),
+            (_, None) => escape_html(&mut output, &heading).unwrap(),
+        }
+        output.push_str("</p>\n\n");
+        mark(&output)
+    }
+}
+
+fn close(kind: &Kind) -> String {
+    format!("\n</{}>\n\n", kind.element())
+}
+
+/// Expands `input` onto `output`, recording in `map` where each line came from; `origin` maps
+/// offsets of `input` to those of the text being expanded, which differ within alerts.
+fn expand_into(
+    input: &str,
+    origin: &SourceMap,
+    options: Options,
+    state: &mut State,
+    output: &mut String,
+    map: &mut SourceMap,
+) {
+    let code: Vec<Range<usize>> = code_ranges(input, options);
+    let in_code = |pos: usize| code.iter().any(|range| range.contains(&pos));
+    let lines = lines(input);
+    let mut open: Vec<&Kind> = vec![];
+    let mut i = 0;
+    while i < lines.len() {
+        let (pos, line) = lines[i];
+        let content = line.trim_end_matches(&['\n', '\r'][..]);
+        i += 1;
+        let from = origin.map(pos);
+        if in_code(pos) {
+            map.push(output.len(), from, true);
+            output.push_str(line);
+            continue;
+        }
+        if let Some(rest) = fence(content) {
+            if rest.trim().is_empty() {
+                if let Some(kind) = open.pop() {
+                    map.push(output.len(), from, false);
+                    output.push_str(&close(kind));
+                    continue;
+                }
+            } else {
+                match parse_header(rest) {
+                    Ok(header) => {
+                        map.push(output.len(), from, false);
+                        output.push_str(&state.open(&header));
+                        open.push(header.kind);
+                        continue;
+                    }
+                    Err(e) => state.warnings.push(e),
+                }
+            }
+        } else if let Some(rest) = alert(content) {
+            let header = rest.replacen(']', " ", 1);
+            match parse_header(&header) {
+                Ok(header) => {
+                    let mut quoted = String::new();
+                    let mut quoted_origin = SourceMap::default();
+                    while let Some(line) = lines.get(i).and_then(|&(_, line)| unquote(line)) {
+                        quoted_origin.push(quoted.len(), origin.map(offset_in(input, line)), true);
+                        quoted.push_str(line);
+                        i += 1;
+                    }
+                    map.push(output.len(), from, false);
+                    output.push_str(&state.open(&header));
+                    expand_into(&quoted, &quoted_origin, options, state, output, map);
+                    map.push(output.len(), from, false);
+                    output.push_str(&close(header.kind));
+                    continue;
+                }
+                Err(e) => state.warnings.push(e),
+            }
+        }
+        map.push(output.len(), from, true);
+        output.push_str(line);
+    }
+    for kind in open.into_iter().rev() {
+        state
+            .warnings
+            .push(format!("A :::{} block is never closed by :::", kind.name));
+        map.push(output.len(), origin.map(input.len()), false);
+        output.push_str(&close(kind));
+    }
+}
+
+/// Rewrites the admonitions in `input` outside of code into HTML blocks around their
+/// Markdown, which is parsed as usual. They are written either
+///
+/// ```text
+/// :::theorem{#pythagoras} Pythagoras
+/// $a^2 + b^2 = c^2$
+/// :::
+/// ```
+///
+/// or as GitHub-style alerts, `> [!NOTE]` followed by quoted lines. Blocks can be nested.
+///
+/// Returns the rewritten Markdown, where its lines came from in `input`, and, for each
+/// numbered block, its label and how it is referred to, such as `"pythagoras"` and
+/// `"Theorem 1"`. Mistakes are added to `warnings`.
+pub fn expand(
+    input: &str,
+    options: Options,
+    warnings: &mut Vec<String>,
+) -> (String, HashMap<String, String>, SourceMap) {
+    let mut state = State {
+        numbers: HashMap::new(),
+        labels: HashMap::new(),
+        warnings,
+    };
+    let mut output = String::with_capacity(input.len());
+    let mut map = SourceMap::default();
+    expand_into(
+        input,
+        &SourceMap::default(),
+        options,
+        &mut state,
+        &mut output,
+        &mut map,
+    );
+    (output, state.labels, map)
+}
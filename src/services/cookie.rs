@@ -8,48 +8,277 @@ pub enum CookieError {
     NotFound,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SameSite {
+    Strict,
+    Lax,
+    None,
+}
+
+/// Attributes written alongside a cookie by `CookieService::set_with`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CookieOptions {
+    pub path: Option<String>,
+    pub domain: Option<String>,
+    /// Lifetime in seconds. `None` makes a session cookie.
+    pub max_age: Option<i64>,
+    pub secure: bool,
+    pub same_site: SameSite,
+}
+
+impl Default for CookieOptions {
+    fn default() -> Self {
+        Self {
+            path: Some(String::from("/")),
+            domain: None,
+            max_age: Some(24 * 60 * 60),
+            secure: false,
+            same_site: SameSite::Lax,
+        }
+    }
+}
+
+impl CookieOptions {
+    pub fn path(mut self, path: &str) -> Self {
+        self.path = Some(path.to_string());
+        self
+    }
+
+    pub fn domain(mut self, domain: &str) -> Self {
+        self.domain = Some(domain.to_string());
+        self
+    }
+
+    pub fn max_age(mut self, seconds: i64) -> Self {
+        self.max_age = Some(seconds);
+        self
+    }
+
+    pub fn expires_in(self, duration: chrono::Duration) -> Self {
+        self.max_age(duration.num_seconds())
+    }
+
+    pub fn session(mut self) -> Self {
+        self.max_age = None;
+        self
+    }
+
+    pub fn secure(mut self, secure: bool) -> Self {
+        self.secure = secure;
+        self
+    }
+
+    pub fn same_site(mut self, same_site: SameSite) -> Self {
+        self.same_site = same_site;
+        if same_site == SameSite::None {
+            // Browsers reject `SameSite=None` without `Secure`.
+            self.secure = true;
+        }
+        self
+    }
+
+    fn attributes(&self) -> String {
+        let mut attrs = String::new();
+        if let Some(path) = &self.path {
+            attrs.push_str(&format!("; Path={}", path));
+        }
+        if let Some(domain) = &self.domain {
+            attrs.push_str(&format!("; Domain={}", domain));
+        }
+        if let Some(max_age) = self.max_age {
+            attrs.push_str(&format!("; Max-Age={}", max_age));
+        }
+        attrs.push_str(match self.same_site {
+            SameSite::Strict => "; SameSite=Strict",
+            SameSite::Lax => "; SameSite=Lax",
+            SameSite::None => "; SameSite=None",
+        });
+        if self.secure {
+            attrs.push_str("; Secure");
+        }
+        attrs
+    }
+}
+
 impl CookieService {
     pub fn new() -> Self {
         Self
     }
 
     pub fn set(&self, name: &str, value: &str) {
-        self.set_cookie(name, value, 1);
+        self.set_with(name, value, &CookieOptions::default());
+    }
+
+    pub fn set_with(&self, name: &str, value: &str, options: &CookieOptions) {
+        self.write(&format!(
+            "{}={}{}",
+            encode(name),
+            encode(value),
+            options.attributes()
+        ));
+    }
+
+    pub fn get(&self, name: &str) -> Result<String, CookieError> {
+        parse_cookies(&self.read())
+            .into_iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value)
+            .ok_or(CookieError::NotFound)
+    }
+
+    pub fn remove(&self, name: &str) {
+        self.remove_with(name, &CookieOptions::default());
+    }
+
+    /// Removes a cookie set with a non-default `Path` or `Domain`; those must match to delete it.
+    pub fn remove_with(&self, name: &str, options: &CookieOptions) {
+        self.set_with(name, "", &options.clone().max_age(0));
     }
 
-    pub fn get(&self, _name: &str) -> Result<String, CookieError> {
+    fn html_document() -> web_sys::HtmlDocument {
         let window = web_sys::window().unwrap();
         let document = window.document().unwrap();
-        let html_document = document.dyn_into::<web_sys::HtmlDocument>().unwrap();
-        let cookie_str = html_document.cookie().unwrap();
-        //info!("{}", cookie_str);
-        let cookies: Vec<&str> = cookie_str.split(';').collect();
-        if let Some(cookie) = cookies.iter().nth(0) {
-            if let Some(value) = cookie.split('=').nth(1) {
-                Ok(value.to_string())
-            } else {
-                Err(CookieError::NotFound)
+        document.dyn_into::<web_sys::HtmlDocument>().unwrap()
+    }
+
+    fn read(&self) -> String {
+        Self::html_document().cookie().unwrap_or_default()
+    }
+
+    fn write(&self, cookie: &str) {
+        Self::html_document().set_cookie(cookie).ok();
+    }
+}
+
+/// Splits a `document.cookie` string into decoded `(name, value)` pairs, in order.
+pub fn parse_cookies(jar: &str) -> Vec<(String, String)> {
+    jar.split(';')
+        .filter_map(|pair| {
+            let pair = pair.trim();
+            if pair.is_empty() {
+                return None;
             }
+            let (name, value) = match pair.find('=') {
+                Some(idx) => (&pair[..idx], &pair[idx + 1..]),
+                None => ("", pair),
+            };
+            let value = value.trim();
+            let value = if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+                &value[1..value.len() - 1]
+            } else {
+                value
+            };
+            Some((decode(name.trim()), decode(value)))
+        })
+        .collect()
+}
+
+fn is_cookie_octet(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b"!#$&'()*+-./:<=>?@[]^_`{|}~".contains(&b)
+}
+
+/// Percent-encodes everything outside the RFC 6265 `cookie-octet` set (and `%` itself).
+pub fn encode(input: &str) -> String {
+    let mut out = String::with_capacity(input.len());
+    for &b in input.as_bytes() {
+        if is_cookie_octet(b) {
+            out.push(b as char);
         } else {
-            Err(CookieError::NotFound)
+            out.push_str(&format!("%{:02X}", b));
         }
     }
+    out
+}
 
-    pub fn remove(&self, name: &str) {
-        self.set_cookie(name, "", -1);
+/// Reverses `encode`. Malformed escapes are kept literally.
+pub fn decode(input: &str) -> String {
+    let bytes = input.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = |b: u8| (b as char).to_digit(16);
+            if let (Some(hi), Some(lo)) = (hex(bytes[i + 1]), hex(bytes[i + 2])) {
+                out.push((hi * 16 + lo) as u8);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
     }
+    String::from_utf8_lossy(&out).into_owned()
+}
 
-    fn set_cookie(&self, name: &str, value: &str, days: i32) {
-        let window = web_sys::window().unwrap();
-        let document = window.document().unwrap();
-        let html_document = document.dyn_into::<web_sys::HtmlDocument>().unwrap();
-        html_document
-            .set_cookie(&format!(
-                "{}={}; max-age={}; SameSite=Lax;",
-                name,
-                value,
-                days * 24 * 60 * 60
-            ))
-            .ok();
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find(jar: &str, name: &str) -> Option<String> {
+        parse_cookies(jar)
+            .into_iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value)
+    }
+
+    #[test]
+    fn finds_a_cookie_among_several() {
+        let jar = "theme=dark; token=abc; lang=en";
+        assert_eq!(find(jar, "token").as_deref(), Some("abc"));
+        assert_eq!(find(jar, "lang").as_deref(), Some("en"));
+        assert_eq!(find(jar, "tok"), None);
+        assert_eq!(parse_cookies(jar).len(), 3);
+    }
+
+    #[test]
+    fn trims_whitespace_and_skips_empty_pairs() {
+        let jar = "  a = 1 ;;b=2;  ";
+        assert_eq!(
+            parse_cookies(jar),
+            vec![
+                ("a".to_string(), "1".to_string()),
+                ("b".to_string(), "2".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_equals_signs_in_values() {
+        assert_eq!(
+            find("jwt=a.b=.c==; x=1", "jwt").as_deref(),
+            Some("a.b=.c==")
+        );
+        assert_eq!(
+            parse_cookies("flag"),
+            vec![(String::new(), "flag".to_string())]
+        );
+    }
+
+    #[test]
+    fn unquotes_values() {
+        assert_eq!(
+            find("a=\"quoted value\"", "a").as_deref(),
+            Some("quoted value")
+        );
+        assert_eq!(find("a=\"", "a").as_deref(), Some("\""));
+        assert_eq!(find("a=\"\"", "a").as_deref(), Some(""));
+    }
+
+    #[test]
+    fn encoding_round_trips() {
+        let value = "hello world; 100% ünïcode=\"quoted\",\\";
+        let encoded = encode(value);
+        assert!(encoded.bytes().all(|b| is_cookie_octet(b) || b == b'%'));
+        assert_eq!(decode(&encoded), value);
+        assert_eq!(encode("a b;c"), "a%20b%3Bc");
+        assert_eq!(find(&format!("v={}", encoded), "v").as_deref(), Some(value));
+    }
+
+    #[test]
+    fn keeps_malformed_escapes() {
+        assert_eq!(decode("100%"), "100%");
+        assert_eq!(decode("%4"), "%4");
+        assert_eq!(decode("%zz%41"), "%zzA");
+        assert_eq!(decode("%%41"), "%A");
     }
 }
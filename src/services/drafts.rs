@@ -0,0 +1,104 @@
+use chrono::{NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// `localStorage` keys of drafts are this followed by the post id, or `new` for a post not
+/// created yet.
+const KEY_PREFIX: &str = "draft:";
+const NEW_POST: &str = "new";
+
+/// What the editor had in its fields, kept until the post is saved or the draft discarded.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Draft {
+    pub title: String,
+    pub body: String,
+    pub tags: String,
+    /// In UTC.
+    pub saved_at: NaiveDateTime,
+}
+
+impl Draft {
+    pub fn new(title: String, body: String, tags: String) -> Self {
+        Self {
+            title,
+            body,
+            tags,
+            saved_at: Utc::now().naive_utc(),
+        }
+    }
+
+    /// Whether every field is blank, which is no draft worth keeping.
+    pub fn is_empty(&self) -> bool {
+        self.title.trim().is_empty() && self.body.trim().is_empty() && self.tags.trim().is_empty()
+    }
+}
+
+fn storage() -> Option<web_sys::Storage> {
+    web_sys::window()?.local_storage().ok().flatten()
+}
+
+/// The key of the draft of post `id`, where -1 is a new post as in `MainRoute::Editor`.
+fn key(id: i64) -> String {
+    if id == -1 {
+        format!("{}{}", KEY_PREFIX, NEW_POST)
+    } else {
+        format!("{}{}", KEY_PREFIX, id)
+    }
+}
+
+fn id_of(key: &str) -> Option<i64> {
+    match key.strip_prefix(KEY_PREFIX)? {
+        NEW_POST => Some(-1),
+        id => id.parse().ok(),
+    }
+}
+
+/// The draft kept for post `id`, if any.
+pub fn load(id: i64) -> Option<Draft> {
+    let json = storage()?.get_item(&key(id)).ok().flatten()?;
+    serde_json::from_str(&json).ok()
+}
+
+/// Keeps `draft` as the draft of post `id`, replacing the one before. An empty draft is removed
+/// instead.
+pub fn save(id: i64, draft: &Draft) {
+    if draft.is_empty() {
+        return remove(id);
+    }
+    if let (Some(storage), Ok(json)) = (storage(), serde_json::to_string(draft)) {
+        storage.set_item(&key(id), &json).ok();
+    }
+}
+
+pub fn remove(id: i64) {
+    if let Some(storage) = storage() {
+        storage.remove_item(&key(id)).ok();
+    }
+}
+
+/// Every draft kept, by post id, the most recently saved first.
+pub fn list() -> Vec<(i64, Draft)> {
+    let storage = match storage() {
+        Some(storage) => storage,
+        None => return vec![],
+    };
+    let keys: Vec<String> = (0..storage.length().unwrap_or(0))
+        .filter_map(|i| storage.key(i).ok().flatten())
+        .collect();
+    let mut drafts: Vec<(i64, Draft)> = keys
+        .iter()
+        .filter_map(|key| Some((id_of(key)?, key)))
+        .filter_map(|(id, key)| {
+            let json = storage.get_item(key).ok().flatten()?;
+            Some((id, serde_json::from_str(&json).ok()?))
+        })
+        .collect();
+    drafts.sort_by(|(_, a), (_, b)| b.saved_at.cmp(&a.saved_at));
+    drafts
+}
+
+/// Removes every draft.
+pub fn clear() {
+    for (id, _) in list() {
+        remove(id);
+    }
+}
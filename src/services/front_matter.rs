@@ -0,0 +1,124 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// Settings a post declares in a block at the very start of its body: YAML between `---`
+/// lines, or TOML between `+++` lines. The backend stores the block as part of the body.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct FrontMatter {
+    /// Shown in previews and lists in place of the excerpt.
+    pub summary: Option<String>,
+    /// URL of an image shown above the post and in its preview; see `cover_url`.
+    pub cover: Option<String>,
+    /// Name of the series the post is part of.
+    pub series: Option<String>,
+    /// KaTeX macros for this post, such as `\R: \mathbb{R}`, over the site-wide ones.
+    pub math_macros: HashMap<String, String>,
+    /// Language of the post, such as `ko` or `en`, given to the browser as its `lang`.
+    pub lang: Option<String>,
+    /// Marks the post as unfinished wherever it is shown.
+    pub draft: bool,
+}
+
+impl FrontMatter {
+    /// `cover`, unless it is a URL with a scheme other than `http` or `https`.
+    pub fn cover_url(&self) -> Option<&str> {
+        let cover = self.cover.as_deref()?.trim();
+        let before_path = cover.split(&['/', '?', '#'][..]).next()?;
+        let scheme = before_path.find(':').map(|end| &cover[..end]);
+        match scheme {
+            _ if cover.is_empty() => None,
+            None => Some(cover),
+            Some(scheme)
+                if scheme.eq_ignore_ascii_case("http") || scheme.eq_ignore_ascii_case("https") =>
+            {
+                Some(cover)
+            }
+            Some(_) => None,
+        }
+    }
+}
+
+/// Formats a front matter block can be written in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Format {
+    Yaml,
+    Toml,
+}
+
+/// The format and content of a leading front matter block and the body after it, if `input`
+/// starts with one. A YAML block ends at a `---` or `...` line, a TOML block at a `+++` line.
+///
+/// A post may also start with a thematic break, so `---` only opens a block whose first line
+/// is a `key:` line.
+fn find_block(input: &str) -> Option<(Format, &str, &str)> {
+    let rest = input.strip_prefix('\u{FEFF}').unwrap_or(input);
+    let (format, rest) = match rest.get(..3)? {
+        "---" => (Format::Yaml, &rest[3..]),
+        "+++" => (Format::Toml, &rest[3..]),
+        _ => return None,
+    };
+    let rest = rest
+        .strip_prefix("\r\n")
+        .or_else(|| rest.strip_prefix('\n'))?;
+    let mut start = 0;
+    while start < rest.len() {
+        let end = rest[start..]
+            .find('\n')
+            .map_or(rest.len(), |i| start + i + 1);
+        let line = rest[start..end].trim_end();
+        let closes = match format {
+            Format::Yaml => line == "---" || line == "...",
+            Format::Toml => line == "+++",
+        };
+        if closes {
+            let block = &rest[..start];
+            if format == Format::Yaml && !starts_with_key(block) {
+                return None;
+            }
+            return Some((format, block, &rest[end..]));
+        }
+        start = end;
+    }
+    None
+}
+
+/// Whether the first line of `block` that is not blank is a `key:` line, or there is none.
+fn starts_with_key(block: &str) -> bool {
+    let line = match block.lines().find(|line| !line.trim().is_empty()) {
+        Some(line) => line.trim_end(),
+        None => return true,
+    };
+    match line.find(':') {
+        Some(colon) => {
+            let key = &line[..colon];
+            let after = &line[colon + 1..];
+            !key.is_empty()
+                && key
+                    .chars()
+                    .all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+                && (after.is_empty() || after.starts_with(' ') || after.starts_with('\t'))
+        }
+        None => false,
+    }
+}
+
+/// Splits a leading front matter block off `input`, returning what it declares and the body
+/// after it. Without a block nothing is declared. A block that does not parse is left in the
+/// body, so nothing written is hidden, and its error is returned instead.
+pub fn split(input: &str) -> (Result<FrontMatter, String>, &str) {
+    let parsed = match find_block(input) {
+        Some((_, block, body)) if block.trim().is_empty() => Ok((FrontMatter::default(), body)),
+        Some((Format::Yaml, block, body)) => serde_yaml::from_str(block)
+            .map(|front_matter| (front_matter, body))
+            .map_err(|e| e.to_string()),
+        Some((Format::Toml, block, body)) => toml::from_str(block)
+            .map(|front_matter| (front_matter, body))
+            .map_err(|e| e.to_string()),
+        None => Ok((FrontMatter::default(), input)),
+    };
+    match parsed {
+        Ok((front_matter, body)) => (Ok(front_matter), body),
+        Err(e) => (Err(format!("Front matter: {}", e)), input),
+    }
+}
@@ -0,0 +1,94 @@
+use pulldown_cmark::escape::escape_html;
+use syntect::html::{ClassStyle, ClassedHTMLGenerator};
+use syntect::parsing::{SyntaxDefinition, SyntaxReference, SyntaxSet, SyntaxSetBuilder};
+use syntect::util::LinesWithEndings;
+
+/// Prefix of the classes on highlighted tokens, so that scopes such as `string` or `comment`
+/// never collide with the page's own classes. `highlight.scss` is generated with the same one.
+const CLASS_STYLE: ClassStyle = ClassStyle::SpacedPrefixed { prefix: "hl-" };
+
+const TOML_SYNTAX: &str = include_str!("../../assets/syntaxes/TOML.sublime-syntax");
+
+thread_local! {
+    /// Sublime Text's default syntaxes, loaded on the first highlighted block.
+    static DEFAULTS: SyntaxSet = SyntaxSet::load_defaults_newlines();
+    /// Languages the defaults lack. Kept apart so the defaults need not be linked again.
+    static EXTRAS: SyntaxSet = {
+        let mut builder = SyntaxSetBuilder::new();
+        builder.add(SyntaxDefinition::load_from_str(TOML_SYNTAX, true, None).unwrap());
+        builder.build()
+    };
+}
+
+/// Color scheme of highlighted code blocks, styled by `assets/css/highlight.scss`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Theme {
+    Light,
+    Dark,
+}
+
+impl Theme {
+    /// `"dark"` is the dark theme; anything else is the light one.
+    pub fn from_name(name: &str) -> Self {
+        if name.eq_ignore_ascii_case("dark") {
+            Theme::Dark
+        } else {
+            Theme::Light
+        }
+    }
+
+    fn class(self) -> &'static str {
+        match self {
+            Theme::Light => "highlight-light",
+            Theme::Dark => "highlight-dark",
+        }
+    }
+}
+
+/// Looks a fence's language up by name or file extension, such as `rust`, `rs`, `c++`, `py`,
+/// `sh` or `toml`.
+fn find_syntax<'a>(syntaxes: &'a SyntaxSet, lang: &str) -> Option<&'a SyntaxReference> {
+    if lang.eq_ignore_ascii_case("shell") || lang.eq_ignore_ascii_case("console") {
+        return syntaxes.find_syntax_by_token("sh");
+    }
+    syntaxes.find_syntax_by_token(lang)
+}
+
+fn highlight_with(syntaxes: &SyntaxSet, code: &str, lang: &str) -> Option<String> {
+    let syntax = find_syntax(syntaxes, lang)?;
+    let mut generator = ClassedHTMLGenerator::new_with_class_style(syntax, syntaxes, CLASS_STYLE);
+    for line in LinesWithEndings::from(code) {
+        generator
+            .parse_html_for_line_which_includes_newline(line)
+            .ok()?;
+    }
+    Some(generator.finalize())
+}
+
+/// Highlights `code` as `lang`, or returns `None` if the language is not known.
+///
+/// Tokens get `hl-` prefixed classes instead of inline styles, so the sanitizer keeps them once
+/// marked and the theme is chosen in CSS. With `line_numbers`, a gutter is put next to the code.
+pub fn highlight(code: &str, lang: &str, theme: Theme, line_numbers: bool) -> Option<String> {
+    let html = DEFAULTS
+        .with(|syntaxes| highlight_with(syntaxes, code, lang))
+        .or_else(|| EXTRAS.with(|syntaxes| highlight_with(syntaxes, code, lang)))?;
+    let mut output = format!("<div class=\"highlight {}\">", theme.class());
+    if line_numbers {
+        output.push_str(
+            "<pre class=\"hl-code line-numbers\"><span class=\"gutter\" aria-hidden=\"true\">",
+        );
+        for number in 1..=code.lines().count() {
+            output.push_str(&format!("{}\n", number));
+        }
+        output.push_str("</span>");
+    } else {
+        output.push_str("<pre class=\"hl-code\">");
+    }
+    output.push_str("<code class=\"language-");
+    escape_html(&mut output, lang).unwrap();
+    output.push_str("\">");
+    output.push_str(&html);
+    output.push_str("</code></pre></div>\n");
+    Some(output)
+}
@@ -0,0 +1,128 @@
+use chrono::Utc;
+use serde::Deserialize;
+
+/// Registered claims we care about. The signature is *not* verified; the backend does that.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct Claims {
+    pub exp: Option<i64>,
+    pub iat: Option<i64>,
+    pub sub: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JwtError {
+    Malformed,
+    InvalidBase64,
+    InvalidPayload,
+}
+
+/// Decodes the payload segment of a JWT.
+pub fn decode(token: &str) -> Result<Claims, JwtError> {
+    let mut parts = token.split('.');
+    let payload = match (parts.next(), parts.next(), parts.next(), parts.next()) {
+        (Some(_), Some(payload), Some(_), None) => payload,
+        _ => return Err(JwtError::Malformed),
+    };
+    let bytes = base64::decode_config(payload.trim_end_matches('='), base64::URL_SAFE_NO_PAD)
+        .map_err(|_| JwtError::InvalidBase64)?;
+    serde_json::from_slice(&bytes).map_err(|_| JwtError::InvalidPayload)
+}
+
+/// Unix timestamp (seconds) at which the token expires, if it says so.
+pub fn expires_at(token: &str) -> Option<i64> {
+    decode(token).ok()?.exp
+}
+
+/// Seconds until expiry; negative once expired. `None` if the token has no `exp`.
+pub fn seconds_left(token: &str) -> Option<i64> {
+    expires_at(token).map(|exp| exp - Utc::now().timestamp())
+}
+
+/// A token we cannot decode counts as expired; one without `exp` never expires.
+pub fn is_expired(token: &str) -> bool {
+    match decode(token) {
+        Ok(claims) => claims
+            .exp
+            .map_or(false, |exp| exp <= Utc::now().timestamp()),
+        Err(_) => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token(payload: &str, config: base64::Config) -> String {
+        format!(
+            "eyJhbGciOiJIUzI1NiJ9.{}.signature",
+            base64::encode_config(payload, config)
+        )
+    }
+
+    #[test]
+    fn decodes_claims() {
+        let claims = decode(&token(
+            r#"{"exp":1700000000,"iat":1690000000,"sub":"admin"}"#,
+            base64::URL_SAFE_NO_PAD,
+        ))
+        .unwrap();
+        assert_eq!(claims.exp, Some(1_700_000_000));
+        assert_eq!(claims.iat, Some(1_690_000_000));
+        assert_eq!(claims.sub.as_deref(), Some("admin"));
+    }
+
+    #[test]
+    fn decodes_with_or_without_padding() {
+        // Two bytes short of a multiple of three, so padded base64 ends in `==`.
+        let payload = r#"{"exp":1,"sub":"a???"}"#;
+        let padded = token(payload, base64::URL_SAFE);
+        let unpadded = token(payload, base64::URL_SAFE_NO_PAD);
+        assert!(padded.contains("=."));
+        assert!(!unpadded.contains("=."));
+        assert!(unpadded.contains('_'));
+        assert_eq!(expires_at(&padded), Some(1));
+        assert_eq!(expires_at(&unpadded), Some(1));
+        // `???` encodes to a `_`, which standard base64 would reject.
+        assert_eq!(decode(&unpadded).unwrap().sub.as_deref(), Some("a???"));
+    }
+
+    #[test]
+    fn rejects_malformed_tokens() {
+        assert_eq!(decode("").unwrap_err(), JwtError::Malformed);
+        assert_eq!(decode("header.payload").unwrap_err(), JwtError::Malformed);
+        assert_eq!(decode("a.b.c.d").unwrap_err(), JwtError::Malformed);
+        assert_eq!(decode("a.!!!.c").unwrap_err(), JwtError::InvalidBase64);
+        assert_eq!(
+            decode(&token("not json", base64::URL_SAFE_NO_PAD)).unwrap_err(),
+            JwtError::InvalidPayload
+        );
+        assert!(is_expired("header.payload"));
+        assert_eq!(seconds_left("header.payload"), None);
+    }
+
+    #[test]
+    fn token_without_exp_never_expires() {
+        let token = token(r#"{"sub":"admin"}"#, base64::URL_SAFE_NO_PAD);
+        assert_eq!(expires_at(&token), None);
+        assert_eq!(seconds_left(&token), None);
+        assert!(!is_expired(&token));
+    }
+
+    #[test]
+    fn expires_once_exp_has_passed() {
+        let now = Utc::now().timestamp();
+        let expired = token(
+            &format!(r#"{{"exp":{}}}"#, now - 60),
+            base64::URL_SAFE_NO_PAD,
+        );
+        let valid = token(
+            &format!(r#"{{"exp":{}}}"#, now + 3600),
+            base64::URL_SAFE_NO_PAD,
+        );
+        assert!(is_expired(&expired));
+        assert!(seconds_left(&expired).unwrap() <= -60);
+        assert!(!is_expired(&valid));
+        let left = seconds_left(&valid).unwrap();
+        assert!(left > 3500 && left <= 3600);
+    }
+}
@@ -1,4 +1,12 @@
+pub mod admonition;
 pub mod api;
 pub mod cookie;
+pub mod drafts;
+pub mod front_matter;
+pub mod highlight;
+pub mod jwt;
 pub mod render;
 pub mod router;
+pub mod sanitize;
+pub mod store;
+pub mod token;
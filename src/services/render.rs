@@ -1,32 +1,1356 @@
+use super::admonition;
+use super::front_matter::{self, FrontMatter};
+use super::highlight::{highlight, Theme};
+use super::router::{self, MainRoute};
+use super::sanitize::{mark, sanitize, strip_marks, unmark};
+use crate::config;
+use crate::constants::{MAX_LEN_PREVIEW, WORDS_PER_MINUTE};
 use katex_wasmbind::KaTeXOptions;
-use pulldown_cmark::{html, Options, Parser};
-use yew::prelude::*;
-
-pub fn render(input: String) -> String {
-    let d = KaTeXOptions::inline_mode();
-    let mut real_output = String::new();
-    let mut stack: Vec<usize> = vec![];
-    let mut prev = 0;
-    for (idx, _) in input.match_indices("$") {
-        if stack.is_empty() {
-            if let Some(s) = input.get(prev..idx) {
-                real_output.push_str(&s);
-            }
-            stack.push(idx);
+use pulldown_cmark::escape::escape_html;
+use pulldown_cmark::{html, CodeBlockKind, CowStr, Event, Options, Parser, Tag};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::ops::Range;
+
+/// Brackets the index of a rendered formula while the Markdown around it is parsed. Private-use
+/// characters are never special to Markdown, so a placeholder always lands in one `Text` event.
+const MATH_START: char = '\u{E000}';
+const MATH_END: char = '\u{E001}';
+
+/// Put after each top-level block while events are transformed, so that `RenderCache` can
+/// split them into the blocks it keeps the HTML of.
+const BLOCK_END: &str = "\u{E002}";
+
+/// Opens the marks `mark_source_lines` puts before blocks, up to the line.
+const SOURCE_LINE: &str = "<span class=\"source-line\" data-source-line=\"";
+
+/// Opens a reference to another post: `[[post:42]]` links to it, `[[post:42|label]]` with a
+/// label of its own, and `![[post:42]]` alone in a paragraph embeds a preview of it.
+const POST_REF_OPEN: &str = "[[post:";
+/// Opens a reference to a numbered block in the same post, such as `[[ref:pythagoras]]`.
+const BLOCK_REF_OPEN: &str = "[[ref:";
+const REF_CLOSE: &str = "]]";
+
+/// Put before the ids of headings and labelled blocks, which are made from author text, so that
+/// they can't name a global on `window` or `document`, nor take an id the page itself uses.
+pub const ID_PREFIX: &str = "user-content-";
+
+/// Markdown extensions on top of CommonMark. All of them are on by default.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RenderOptions {
+    /// `~~struck~~` text.
+    pub strikethrough: bool,
+    /// GFM pipe tables, with column alignment.
+    pub tables: bool,
+    /// `[^name]` references to `[^name]: ..` definitions, linked both ways.
+    pub footnotes: bool,
+    /// `- [ ]` and `- [x]` list items, shown as read-only checkboxes.
+    pub task_lists: bool,
+    /// Curly quotes, dashes from `--` and `---`, and `...` as an ellipsis.
+    pub smart_punctuation: bool,
+    /// Note, warning, theorem, proof and other blocks, written `:::kind` .. `:::` or as
+    /// `> [!KIND]` quotes; see `admonition::expand`.
+    pub admonitions: bool,
+    /// Syntax highlighting of fenced code blocks tagged with a known language.
+    pub highlight: bool,
+    /// A line number gutter next to highlighted code.
+    pub line_numbers: bool,
+    /// Colors of highlighted code.
+    pub code_theme: Theme,
+    /// Length of `RenderedPost::excerpt`, in words.
+    pub excerpt_words: usize,
+    /// An empty `span.source-line` before each block, its `data-source-line` the line of the
+    /// input the block starts on, counting from 0. Off by default; the editor uses it to keep
+    /// its source and preview scrolled to the same place.
+    pub source_lines: bool,
+    /// KaTeX macros by name, such as `\R` for `\mathbb{R}`. A post's front matter can add more
+    /// and override these.
+    pub macros: HashMap<String, String>,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            strikethrough: true,
+            tables: true,
+            footnotes: true,
+            task_lists: true,
+            smart_punctuation: true,
+            admonitions: true,
+            highlight: true,
+            line_numbers: false,
+            code_theme: Theme::Light,
+            excerpt_words: MAX_LEN_PREVIEW,
+            source_lines: false,
+            macros: HashMap::new(),
+        }
+    }
+}
+
+impl RenderOptions {
+    /// Every extension on, with code highlighted and math macros defined as `config.json` says.
+    pub fn configured() -> Self {
+        let config = config::get();
+        Self {
+            line_numbers: config.code_line_numbers,
+            code_theme: Theme::from_name(&config.code_theme),
+            excerpt_words: config.preview_length,
+            macros: config.math_macros.clone(),
+            ..Self::default()
+        }
+    }
+
+    /// Hashes the options, `macros` sorted by name, for `RenderCache` to tell renders apart.
+    fn key(&self) -> u64 {
+        let mut macros: Vec<_> = self.macros.iter().collect();
+        macros.sort();
+        hash_of((
+            [
+                self.strikethrough,
+                self.tables,
+                self.footnotes,
+                self.task_lists,
+                self.smart_punctuation,
+                self.admonitions,
+                self.highlight,
+                self.line_numbers,
+                self.source_lines,
+            ],
+            self.code_theme,
+            self.excerpt_words,
+            macros,
+        ))
+    }
+
+    fn parser_options(&self) -> Options {
+        let mut options = Options::empty();
+        options.set(Options::ENABLE_STRIKETHROUGH, self.strikethrough);
+        options.set(Options::ENABLE_TABLES, self.tables);
+        options.set(Options::ENABLE_FOOTNOTES, self.footnotes);
+        options.set(Options::ENABLE_TASKLISTS, self.task_lists);
+        options.set(Options::ENABLE_SMART_PUNCTUATION, self.smart_punctuation);
+        options
+    }
+}
+
+/// A formula rendered ahead of the Markdown, and the TeX it was written as.
+struct Formula {
+    tex: String,
+    html: String,
+}
+
+/// A heading, as listed in a post's table of contents.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Heading {
+    /// 1 for `#`, up to 6 for `######`.
+    pub level: u32,
+    /// The heading's element id, so `#id` links to it.
+    pub id: String,
+    /// Plain text, with TeX source in place of math.
+    pub title: String,
+}
+
+/// A rendered post body, with what pages show about it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RenderedPost {
+    /// Sanitized, so it is safe to hand to `set_inner_html`.
+    pub html: String,
+    /// What is read of the post: its text without markup, math or code, a line per block.
+    pub text: String,
+    /// Words of `text`; see `count_words`.
+    pub words: usize,
+    /// Characters of `text` other than whitespace, the usual length measure for Korean.
+    pub chars: usize,
+    /// Estimated minutes to read, at least 1.
+    pub reading_minutes: usize,
+    /// The front matter's `summary`, or else the first `RenderOptions::excerpt_words` words of
+    /// `text`, ending in `…` if cut.
+    pub excerpt: String,
+    pub toc: Vec<Heading>,
+    /// What the post declares about itself, left out of `html` and `text`.
+    pub front_matter: FrontMatter,
+    /// Ids of the posts linked or embedded with `[[post:ID]]`, in order of first mention.
+    pub post_links: Vec<i64>,
+    /// Mistakes worth telling the author about, such as a macro KaTeX cannot use. The post is
+    /// rendered regardless.
+    pub warnings: Vec<String>,
+}
+
+impl RenderedPost {
+    /// Such as "3 min read · 512 words".
+    pub fn reading_time(&self) -> String {
+        format!("{} min read · {} words", self.reading_minutes, self.words)
+    }
+}
+
+/// A piece of a post body, split on math delimiters.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Token<'a> {
+    /// Markdown, including backslash escapes such as `\$`, which Markdown itself unescapes.
+    Text(&'a str),
+    /// `$..$` and `\(..\)` are inline; `$$..$$` and `\[..\]` are display math.
+    Math { tex: &'a str, display: bool },
+    /// An opening `$$`, `\(` or `\[` that is never closed.
+    Unterminated { open: &'a str, close: &'static str },
+}
+
+/// Finds `close` in `input`, skipping backslash escapes so `\$` never closes `$..$`.
+fn find_close(input: &str, close: &str) -> Option<usize> {
+    let bytes = input.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        if input[i..].starts_with(close) {
+            return Some(i);
+        }
+        i += if bytes[i] == b'\\' { 2 } else { 1 };
+        while i < bytes.len() && !input.is_char_boundary(i) {
+            i += 1;
+        }
+    }
+    None
+}
+
+/// Closes a `$..$` opened just before `input`, pandoc style: the math may not start or end
+/// with whitespace, the closing `$` may not be followed by a digit and a blank line ends the
+/// search. This keeps prices like "$5 and $10" as text.
+fn find_inline_dollar(input: &str) -> Option<usize> {
+    if input.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let limit = input.find("\n\n").unwrap_or_else(|| input.len());
+    let mut from = 0;
+    while let Some(j) = find_close(&input[from..limit], "$") {
+        let j = from + j;
+        let after = input[j + 1..].chars().next();
+        if j > 0
+            && !input[..j].ends_with(char::is_whitespace)
+            && !after.map_or(false, |c| c.is_ascii_digit())
+        {
+            return Some(j);
+        }
+        from = j + 1;
+    }
+    None
+}
+
+/// Splits `input` into Markdown text and math.
+///
+/// A lone `$` that does not open inline math is left as text; an unclosed `$$`, `\(` or `\[`
+/// becomes `Token::Unterminated` and the text after it is kept.
+pub fn tokenize(input: &str) -> Vec<Token<'_>> {
+    let bytes = input.as_bytes();
+    let mut tokens = vec![];
+    let mut text_start = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        let (open, close, display) = match (bytes[i], bytes.get(i + 1)) {
+            (b'\\', Some(b'(')) => ("\\(", "\\)", false),
+            (b'\\', Some(b'[')) => ("\\[", "\\]", true),
+            (b'\\', Some(_)) => {
+                i += 2;
+                continue;
+            }
+            (b'$', Some(b'$')) => ("$$", "$$", true),
+            (b'$', _) => ("$", "$", false),
+            _ => {
+                i += 1;
+                continue;
+            }
+        };
+        let body = &input[i + open.len()..];
+        let end = if open == "$" {
+            find_inline_dollar(body)
+        } else {
+            find_close(body, close)
+        };
+        if end.is_none() && open == "$" {
+            // Not math after all; the `$` stays part of the surrounding text.
+            i += 1;
+            continue;
+        }
+        if text_start < i {
+            tokens.push(Token::Text(&input[text_start..i]));
+        }
+        match end {
+            Some(end) => {
+                tokens.push(Token::Math {
+                    tex: &body[..end],
+                    display,
+                });
+                i += open.len() + end + close.len();
+            }
+            None => {
+                tokens.push(Token::Unterminated { open, close });
+                i += open.len();
+            }
+        }
+        text_start = i;
+    }
+    if text_start < bytes.len() {
+        tokens.push(Token::Text(&input[text_start..]));
+    }
+    tokens
+}
+
+/// Renders TeX with KaTeX, expanding `macros`. Invalid TeX comes back as KaTeX's own red
+/// `.katex-error` span.
+pub fn render_math(tex: &str, display: bool, macros: &HashMap<String, String>) -> String {
+    let mut options = if display {
+        KaTeXOptions::display_mode()
+    } else {
+        KaTeXOptions::inline_mode()
+    };
+    options.throw_on_error = false;
+    options.macros = macros.clone();
+    mark(&options.render(tex))
+}
+
+/// Checks a macro definition the way KaTeX reads it: `name` is a backslash and either letters
+/// or one other character, and `expansion` has balanced braces and only `#1` to `#9` (or `##`)
+/// as parameters.
+pub fn check_macro(name: &str, expansion: &str) -> Result<(), String> {
+    let command = name.strip_prefix('\\').unwrap_or_default();
+    let named = match command.chars().count() {
+        0 => false,
+        1 => true,
+        _ => command.chars().all(|c| c.is_ascii_alphabetic()),
+    };
+    if !named {
+        return Err(format!("{} is not a command name such as \\R", name));
+    }
+    let mut depth = 0;
+    let mut chars = expansion.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '{' => depth += 1,
+            '}' if depth == 0 => return Err(format!("{} has a }} that closes nothing", name)),
+            '}' => depth -= 1,
+            '#' => match chars.next() {
+                Some('1'..='9') | Some('#') => {}
+                _ => {
+                    return Err(format!(
+                        "{} has a # not followed by a parameter number from 1 to 9",
+                        name
+                    ))
+                }
+            },
+            _ => {}
+        }
+    }
+    if depth > 0 {
+        return Err(format!("{} has a {{ that is never closed", name));
+    }
+    Ok(())
+}
+
+/// The site's macros overridden by the post's, without those `check_macro` rejects. Each one
+/// rejected is reported once in `warnings` rather than as an error in every formula using it.
+fn usable_macros(
+    site: &HashMap<String, String>,
+    post: &HashMap<String, String>,
+    warnings: &mut Vec<String>,
+) -> HashMap<String, String> {
+    let mut macros = site.clone();
+    macros.extend(post.clone());
+    let mut names: Vec<String> = macros.keys().cloned().collect();
+    names.sort();
+    for name in names {
+        if let Err(e) = check_macro(&name, &macros[&name]) {
+            warnings.push(format!("Macro {}", e));
+            macros.remove(&name);
+        }
+    }
+    macros
+}
+
+/// A visible `.math-error` span showing `source`, with `message` as its tooltip.
+pub fn math_error(source: &str, message: &str) -> String {
+    let mut output = String::from("<span class=\"math-error\" title=\"");
+    escape_html(&mut output, message).unwrap();
+    output.push_str("\">");
+    escape_html(&mut output, source).unwrap();
+    output.push_str("</span>");
+    mark(&output)
+}
+
+/// Byte ranges of code blocks and code spans, in order. Math and admonitions are not looked
+/// for inside them.
+pub fn code_ranges(input: &str, options: Options) -> Vec<Range<usize>> {
+    Parser::new_ext(input, options)
+        .into_offset_iter()
+        .filter_map(|(event, range)| match event {
+            Event::Start(Tag::CodeBlock(_)) | Event::Code(_) => Some(range),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Where the pieces of a rewritten text came from in the text it was rewritten from, so what
+/// is parsed out of it can be traced back to its line.
+#[derive(Clone, Debug, Default)]
+pub struct SourceMap {
+    /// The offset each piece starts at, the offset it came from, and whether it was copied as
+    /// is rather than generated.
+    pieces: Vec<(usize, usize, bool)>,
+}
+
+impl SourceMap {
+    /// Records that the rewritten text from `at` on comes from `from` on. Pieces are pushed in
+    /// order.
+    pub fn push(&mut self, at: usize, from: usize, copied: bool) {
+        self.pieces.push((at, from, copied));
+    }
+
+    /// The offset `offset` of the rewritten text came from. Generated text maps to where it
+    /// was generated from, and an empty map maps every offset to itself.
+    pub fn map(&self, offset: usize) -> usize {
+        let i = match self.pieces.binary_search_by(|&(at, _, _)| at.cmp(&offset)) {
+            Ok(i) => Some(i),
+            Err(i) => i.checked_sub(1),
+        };
+        match i.map(|i| self.pieces[i]) {
+            Some((at, from, true)) => from + (offset - at),
+            Some((_, from, false)) => from,
+            None => offset,
+        }
+    }
+}
+
+/// Where `inner`, a slice of `outer`, starts in it.
+pub fn offset_in(outer: &str, inner: &str) -> usize {
+    inner.as_ptr() as usize - outer.as_ptr() as usize
+}
+
+/// Renders the math in `input` outside of `code` into `formulas` and replaces each formula
+/// with a placeholder holding its index, recording in `map` where the output came from.
+fn extract_math(
+    input: &str,
+    code: &[Range<usize>],
+    macros: &HashMap<String, String>,
+    formulas: &mut Vec<Formula>,
+    cache: &mut Memo,
+    map: &mut SourceMap,
+) -> String {
+    let mut sorted: Vec<_> = macros.iter().collect();
+    sorted.sort();
+    let macros_key = hash_of(sorted);
+    let mut output = String::with_capacity(input.len());
+    let mut pos = 0;
+    let end = input.len()..input.len();
+    for range in code.iter().chain(std::iter::once(&end)) {
+        if range.start < pos {
+            continue;
+        }
+        let mut from = pos;
+        for token in tokenize(&input[pos..range.start]) {
+            let formula = match token {
+                Token::Text(text) => {
+                    from = offset_in(input, text);
+                    map.push(output.len(), from, true);
+                    output.push_str(text);
+                    from += text.len();
+                    continue;
+                }
+                Token::Math { tex, display } => Formula {
+                    tex: tex.to_string(),
+                    html: cache.get_or_insert_with(hash_of((tex, display, macros_key)), || {
+                        render_math(tex, display, macros)
+                    }),
+                },
+                Token::Unterminated { open, close } => Formula {
+                    tex: open.to_string(),
+                    html: math_error(
+                        open,
+                        &format!("Unterminated math: {} is never closed by {}", open, close),
+                    ),
+                },
+            };
+            if let Token::Math { tex, .. } = token {
+                from = offset_in(input, tex);
+            }
+            map.push(output.len(), from, false);
+            output.push(MATH_START);
+            output.push_str(&formulas.len().to_string());
+            output.push(MATH_END);
+            formulas.push(formula);
+        }
+        map.push(output.len(), range.start, true);
+        output.push_str(&input[range.clone()]);
+        pos = range.end;
+    }
+    output
+}
+
+/// Text between math placeholders, or the formula a placeholder stands for.
+enum Piece<'a> {
+    Text(&'a str),
+    Math(&'a Formula),
+}
+
+fn split_math<'a>(text: &'a str, formulas: &'a [Formula]) -> Vec<Piece<'a>> {
+    let mut pieces = vec![];
+    let mut rest = text;
+    while let Some(start) = rest.find(MATH_START) {
+        let after = &rest[start + MATH_START.len_utf8()..];
+        let formula = after.find(MATH_END).and_then(|end| {
+            let formula = formulas.get(after[..end].parse::<usize>().ok()?)?;
+            Some((formula, &after[end + MATH_END.len_utf8()..]))
+        });
+        let (formula, next) = match formula {
+            Some(formula) => formula,
+            None => break,
+        };
+        if start > 0 {
+            pieces.push(Piece::Text(&rest[..start]));
+        }
+        pieces.push(Piece::Math(formula));
+        rest = next;
+    }
+    if !rest.is_empty() {
+        pieces.push(Piece::Text(rest));
+    }
+    pieces
+}
+
+/// Splits a `Text` event at math placeholders, turning each into the formula's HTML.
+fn insert_math<'a>(text: CowStr<'a>, formulas: &[Formula]) -> Vec<Event<'a>> {
+    if !text.contains(MATH_START) {
+        return vec![Event::Text(text)];
+    }
+    split_math(&text, formulas)
+        .into_iter()
+        .map(|piece| match piece {
+            Piece::Text(text) => Event::Text(text.to_string().into()),
+            Piece::Math(formula) => Event::Html(formula.html.clone().into()),
+        })
+        .collect()
+}
+
+/// The text of a post as read, a line per block. Code and math are left out.
+fn plain_text(events: &[Event<'_>], formulas: &[Formula]) -> String {
+    let mut text = String::new();
+    let mut in_code = false;
+    for event in events {
+        match event {
+            Event::Start(Tag::CodeBlock(_)) => in_code = true,
+            Event::End(Tag::CodeBlock(_)) => in_code = false,
+            Event::Text(content) if !in_code => {
+                for piece in split_math(content, formulas) {
+                    if let Piece::Text(content) = piece {
+                        text.push_str(content);
+                    }
+                }
+            }
+            Event::SoftBreak | Event::HardBreak => text.push(' '),
+            Event::End(Tag::Paragraph)
+            | Event::End(Tag::Heading(_))
+            | Event::End(Tag::Item)
+            | Event::End(Tag::TableCell) => text.push('\n'),
+            _ => {}
+        }
+    }
+    text.lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Han, Hiragana and Katakana, whose scripts are written without spaces between words.
+fn is_unspaced(c: char) -> bool {
+    matches!(
+        c,
+        '\u{3040}'..='\u{30FF}'
+            | '\u{3400}'..='\u{4DBF}'
+            | '\u{4E00}'..='\u{9FFF}'
+            | '\u{F900}'..='\u{FAFF}'
+    )
+}
+
+/// Counts the words of `text`. Korean separates words with spaces, so a Hangul word counts
+/// once like an English one, while every Han or kana character counts as a word of its own.
+/// Runs of punctuation are not words.
+pub fn count_words(text: &str) -> usize {
+    text.split_whitespace()
+        .map(|word| {
+            let unspaced = word.chars().filter(|&c| is_unspaced(c)).count();
+            let spaced = word.chars().any(|c| c.is_alphanumeric() && !is_unspaced(c));
+            unspaced + spaced as usize
+        })
+        .sum()
+}
+
+/// The first `words` words of `text`, ending in `…` if there are more.
+fn excerpt(text: &str, words: usize) -> String {
+    let mut rest = text.split_whitespace();
+    let mut excerpt = rest.by_ref().take(words).collect::<Vec<_>>().join(" ");
+    if rest.next().is_some() {
+        excerpt.push('…');
+    }
+    excerpt
+}
+
+/// Turns a heading's title into an id the way GitHub does: lowercased, with letters (Hangul
+/// included) and digits kept, spaces made into `-` and other punctuation dropped.
+pub fn slugify(title: &str) -> String {
+    let slug: String = title
+        .trim()
+        .chars()
+        .flat_map(char::to_lowercase)
+        .filter_map(|c| match c {
+            c if c.is_alphanumeric() || c == '-' || c == '_' => Some(c),
+            c if c.is_whitespace() => Some('-'),
+            _ => None,
+        })
+        .collect();
+    if slug.is_empty() {
+        String::from("section")
+    } else {
+        slug
+    }
+}
+
+/// Gives every heading a unique slug id and a permalink, and lists those with a title in order.
+///
+/// Repeated titles get `-1`, `-2`, ... appended, like on GitHub, and every id starts with
+/// `ID_PREFIX`.
+fn anchor_headings<'a>(
+    events: Vec<Event<'a>>,
+    formulas: &[Formula],
+) -> (Vec<Event<'a>>, Vec<Heading>) {
+    let mut output = Vec::with_capacity(events.len());
+    let mut headings = vec![];
+    let mut ids = HashSet::new();
+    let mut start = None;
+    for event in events {
+        match &event {
+            Event::Start(Tag::Heading(_)) => start = Some(output.len()),
+            Event::End(Tag::Heading(level)) => {
+                if let Some(start) = start.take() {
+                    let title: String = output[start..]
+                        .iter()
+                        .flat_map(|event| match event {
+                            Event::Text(text) => split_math(text, formulas)
+                                .into_iter()
+                                .map(|piece| match piece {
+                                    Piece::Text(text) => text,
+                                    Piece::Math(formula) => &formula.tex,
+                                })
+                                .collect(),
+                            Event::Code(code) => vec![&**code],
+                            _ => vec![],
+                        })
+                        .collect();
+                    let slug = slugify(&title);
+                    let mut id = slug.clone();
+                    let mut repeats = 0;
+                    while !ids.insert(id.clone()) {
+                        repeats += 1;
+                        id = format!("{}-{}", slug, repeats);
+                    }
+                    let id = format!("{}{}", ID_PREFIX, id);
+                    output[start] =
+                        Event::Html(mark(&format!("<h{} id=\"{}\">", level, escaped(&id))).into());
+                    output.push(Event::Html(
+                        mark(&format!(
+                            "<a class=\"anchor\" href=\"#{}\" title=\"Permalink to this section\">#</a></h{}>\n",
+                            escaped(&id),
+                            level
+                        ))
+                        .into(),
+                    ));
+                    if !title.trim().is_empty() {
+                        headings.push(Heading {
+                            level: *level,
+                            id,
+                            title: title.trim().to_string(),
+                        });
+                    }
+                    continue;
+                }
+            }
+            _ => {}
+        }
+        output.push(event);
+    }
+    (output, headings)
+}
+
+fn escaped(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    escape_html(&mut output, text).unwrap();
+    output
+}
+
+/// Joins runs of `Text` events, which pulldown-cmark splits at brackets and punctuation, so a
+/// `[[post:..]]` reference is always found in one piece.
+fn merge_text(events: Vec<Event<'_>>) -> Vec<Event<'_>> {
+    let mut output: Vec<Event> = Vec::with_capacity(events.len());
+    for event in events {
+        match (output.last_mut(), event) {
+            (Some(Event::Text(previous)), Event::Text(text)) => {
+                *previous = format!("{}{}", previous, text).into();
+            }
+            (_, event) => output.push(event),
+        }
+    }
+    output
+}
+
+/// The post id and label of what is between `[[post:` and `]]`, if the id is a number.
+fn parse_post_ref(inner: &str) -> Option<(i64, Option<&str>)> {
+    let mut parts = inner.splitn(2, '|');
+    let id = parts.next()?.trim();
+    if id.is_empty() || !id.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let label = parts
+        .next()
+        .map(str::trim)
+        .filter(|label| !label.is_empty());
+    Some((id.parse().ok()?, label))
+}
+
+/// The post a paragraph of only `![[post:ID]]` embeds.
+fn embedded_post(text: &str) -> Option<i64> {
+    let inner = text
+        .trim()
+        .strip_prefix('!')?
+        .strip_prefix(POST_REF_OPEN)?
+        .strip_suffix(REF_CLOSE)?;
+    parse_post_ref(inner).map(|(id, _)| id)
+}
+
+/// Turns the `[[post:..]]` references in `text` into router links to the posts, and
+/// `[[ref:..]]` references into links to the numbered blocks `labels` lists, such as
+/// "Theorem 2". A reference to no post or block stays as written and is reported in `warnings`.
+fn link_refs(
+    text: &str,
+    labels: &HashMap<String, String>,
+    post_links: &mut Vec<i64>,
+    warnings: &mut Vec<String>,
+) -> Vec<Event<'static>> {
+    let mut output = vec![];
+    let mut rest = text;
+    loop {
+        let (start, open) = match [POST_REF_OPEN, BLOCK_REF_OPEN]
+            .iter()
+            .filter_map(|&open| Some((rest.find(open)?, open)))
+            .min()
+        {
+            Some(found) => found,
+            None => break,
+        };
+        let after = &rest[start + open.len()..];
+        let end = match after.find(REF_CLOSE) {
+            Some(end) => end,
+            None => break,
+        };
+        let inner = &after[..end];
+        let next = &after[end + REF_CLOSE.len()..];
+        let reference = &rest[start..rest.len() - next.len()];
+        let mut before = &rest[..start];
+        let link = if open == POST_REF_OPEN {
+            match parse_post_ref(inner) {
+                Some((id, label)) => {
+                    if !post_links.contains(&id) {
+                        post_links.push(id);
+                    }
+                    // `!` only embeds a post from a paragraph of its own; inline, it is a link.
+                    before = before.strip_suffix('!').unwrap_or(before);
+                    let label = label.map_or_else(|| format!("Post {}", id), str::to_string);
+                    Ok(("post-link", router::path(MainRoute::ViewPost(id)), label))
+                }
+                None => Err(format!(
+                    "{} does not name a post by its number, as in [[post:42]]",
+                    reference
+                )),
+            }
         } else {
-            if let Some(j) = stack.pop() {
-                real_output.push_str(&d.render(input.get((j + 1)..idx).unwrap()));
+            match labels.get(inner.trim()) {
+                Some(heading) => Ok((
+                    "cross-ref",
+                    format!("#{}{}", ID_PREFIX, inner.trim()),
+                    heading.clone(),
+                )),
+                None => Err(format!(
+                    "{} refers to no numbered block; label one with {{#{}}}",
+                    reference,
+                    inner.trim()
+                )),
             }
+        };
+        match link {
+            Ok((class, href, label)) => {
+                if !before.is_empty() {
+                    output.push(Event::Text(before.to_string().into()));
+                }
+                output.push(Event::Html(
+                    mark(&format!(
+                        "<a class=\"{}\" href=\"{}\">",
+                        class,
+                        escaped(&href)
+                    ))
+                    .into(),
+                ));
+                output.push(Event::Text(label.into()));
+                output.push(Event::Html("</a>".into()));
+            }
+            Err(warning) => {
+                warnings.push(warning);
+                output.push(Event::Text(
+                    rest[..rest.len() - next.len()].to_string().into(),
+                ));
+            }
+        }
+        rest = next;
+    }
+    if !rest.is_empty() {
+        output.push(Event::Text(rest.to_string().into()));
+    }
+    output
+}
+
+/// Resolves `[[post:ID]]` and `[[ref:label]]` references outside of code into links, and
+/// paragraphs of only `![[post:ID]]` into `.post-embed` blocks, which pages fill with a preview
+/// of the post. Each block holds a plain link to the post until then.
+fn link_refs_in<'a>(
+    events: Vec<Event<'a>>,
+    labels: &HashMap<String, String>,
+    post_links: &mut Vec<i64>,
+    warnings: &mut Vec<String>,
+) -> Vec<Event<'a>> {
+    let mut output = Vec::with_capacity(events.len());
+    let mut in_code = false;
+    let mut events = merge_text(events).into_iter().peekable();
+    while let Some(event) = events.next() {
+        match event {
+            Event::Start(Tag::CodeBlock(_)) => in_code = true,
+            Event::End(Tag::CodeBlock(_)) => in_code = false,
+            Event::Text(text)
+                if !in_code && (text.contains(POST_REF_OPEN) || text.contains(BLOCK_REF_OPEN)) =>
+            {
+                let embed = match (output.last(), events.peek()) {
+                    (Some(Event::Start(Tag::Paragraph)), Some(Event::End(Tag::Paragraph))) => {
+                        embedded_post(&text)
+                    }
+                    _ => None,
+                };
+                match embed {
+                    Some(id) => {
+                        output.pop();
+                        events.next();
+                        output.push(Event::Html(
+                            mark(&format!(
+                                "<div class=\"post-embed\"><a class=\"post-link\" href=\"{}\">Post {}</a></div>\n",
+                                escaped(&router::path(MainRoute::ViewPost(id))),
+                                id
+                            ))
+                            .into(),
+                        ));
+                        if !post_links.contains(&id) {
+                            post_links.push(id);
+                        }
+                    }
+                    None => output.extend(link_refs(&text, labels, post_links, warnings)),
+                }
+                continue;
+            }
+            _ => {}
+        }
+        output.push(event);
+    }
+    output
+}
+
+/// Numbers footnotes by first appearance, as pulldown-cmark does, and links every reference to
+/// its definition and each definition back to its first reference.
+fn link_footnotes(events: Vec<Event<'_>>) -> Vec<Event<'_>> {
+    let referenced: HashSet<CowStr> = events
+        .iter()
+        .filter_map(|event| match event {
+            Event::FootnoteReference(name) => Some(name.clone()),
+            _ => None,
+        })
+        .collect();
+    let mut numbers = HashMap::new();
+    let mut references = HashMap::new();
+    let mut output = Vec::with_capacity(events.len());
+    for event in events {
+        let next = numbers.len() + 1;
+        match event {
+            Event::FootnoteReference(name) => {
+                let number = *numbers.entry(name.clone()).or_insert(next);
+                let count = references.entry(name.clone()).or_insert(0);
+                *count += 1;
+                let id = match *count {
+                    1 => format!("fnref-{}", name),
+                    count => format!("fnref-{}-{}", name, count),
+                };
+                output.push(Event::Html(
+                    mark(&format!(
+                        "<sup class=\"footnote-reference\" id=\"{}\"><a href=\"#fn-{}\">{}</a></sup>",
+                        escaped(&id),
+                        escaped(&name),
+                        number
+                    ))
+                    .into(),
+                ));
+            }
+            Event::Start(Tag::FootnoteDefinition(name)) => {
+                let number = *numbers.entry(name.clone()).or_insert(next);
+                output.push(Event::Html(
+                    mark(&format!(
+                        "\n<div class=\"footnote-definition\" id=\"fn-{}\"><sup class=\"footnote-definition-label\">{}</sup>",
+                        escaped(&name),
+                        number
+                    ))
+                    .into(),
+                ));
+            }
+            Event::End(Tag::FootnoteDefinition(name)) => {
+                if referenced.contains(&name) {
+                    let backref = Event::Html(
+                        mark(&format!(
+                            " <a class=\"footnote-backref\" href=\"#fnref-{}\" title=\"Back to text\">\u{21A9}</a>",
+                            escaped(&name)
+                        ))
+                        .into(),
+                    );
+                    // Inside the last paragraph, so the arrow does not get a line of its own.
+                    match output.last() {
+                        Some(Event::End(Tag::Paragraph)) => {
+                            output.insert(output.len() - 1, backref)
+                        }
+                        _ => output.push(backref),
+                    }
+                }
+                output.push(Event::Html("</div>\n".into()));
+            }
+            event => output.push(event),
+        }
+    }
+    output
+}
+
+/// Gives list items starting with a task list checkbox, and the lists holding them, the
+/// classes GitHub's Markdown stylesheet uses to drop their bullets.
+fn mark_task_lists(events: Vec<Event<'_>>) -> Vec<Event<'_>> {
+    let mut output = Vec::with_capacity(events.len());
+    let mut lists = vec![];
+    let mut item = None;
+    for event in events {
+        match event {
+            Event::Start(Tag::List(_)) => lists.push(output.len()),
+            Event::End(Tag::List(_)) => {
+                lists.pop();
+            }
+            Event::Start(Tag::Item) => item = Some(output.len()),
+            Event::TaskListMarker(_) => {
+                if let Some(i) = item.take() {
+                    output[i] = Event::Html(mark("<li class=\"task-list-item\">").into());
+                }
+                if let Some(&i) = lists.last() {
+                    let open = match output[i] {
+                        Event::Start(Tag::List(None)) => {
+                            "<ul class=\"contains-task-list\">\n".to_string()
+                        }
+                        Event::Start(Tag::List(Some(1))) => {
+                            "<ol class=\"contains-task-list\">\n".to_string()
+                        }
+                        Event::Start(Tag::List(Some(start))) => {
+                            format!("<ol class=\"contains-task-list\" start=\"{}\">\n", start)
+                        }
+                        _ => String::new(),
+                    };
+                    if !open.is_empty() {
+                        output[i] = Event::Html(mark(&open).into());
+                    }
+                }
+            }
+            _ => {}
+        }
+        output.push(event);
+    }
+    output
+}
+
+/// Puts a `span.source-line` holding `line_of` its offset before each block outside of the
+/// others, including those within admonitions, which are HTML blocks around Markdown. Only
+/// the first line of an HTML block is marked, as the next could be within a tag.
+fn mark_source_lines<'a>(
+    parser: impl Iterator<Item = (Event<'a>, Range<usize>)>,
+    line_of: impl Fn(usize) -> usize,
+) -> Vec<Event<'a>> {
+    let mut output = vec![];
+    let mut depth = 0;
+    // Where the last line of HTML outside of blocks ended, which the next line of the same
+    // HTML block starts at.
+    let mut html_end = None;
+    for (event, range) in parser {
+        let starts_block = depth == 0
+            && match &event {
+                // A mark between footnote definitions would keep the first of them from being
+                // told apart by the style sheet.
+                Event::Start(Tag::FootnoteDefinition(_)) => false,
+                Event::Start(_) => true,
+                Event::Html(html) => {
+                    html_end != Some(range.start) && !html.trim_start().starts_with("</")
+                }
+                _ => false,
+            };
+        if starts_block {
+            output.push(Event::Html(
+                mark(&format!(
+                    "{}{}\"></span>",
+                    SOURCE_LINE,
+                    line_of(range.start)
+                ))
+                .into(),
+            ));
+        }
+        html_end = match event {
+            Event::Html(_) if depth == 0 => Some(range.end),
+            _ => None,
+        };
+        match event {
+            Event::Start(_) => depth += 1,
+            Event::End(_) => depth -= 1,
+            _ => {}
         }
-        prev = idx + 1;
+        output.push(event);
     }
-    if let Some(s) = input.get(prev..) {
-        real_output.push_str(&s);
+    output
+}
+
+/// Puts a `BLOCK_END` after each top-level block of `events`.
+fn end_blocks(events: Vec<Event<'_>>) -> Vec<Event<'_>> {
+    let mut output = Vec::with_capacity(events.len());
+    let mut depth = 0;
+    for event in events {
+        match event {
+            Event::Start(_) => depth += 1,
+            Event::End(_) => depth -= 1,
+            _ => {}
+        }
+        let ends_block = depth == 0 && matches!(event, Event::End(_) | Event::Rule);
+        output.push(event);
+        if ends_block {
+            output.push(Event::Html(BLOCK_END.into()));
+        }
+    }
+    output
+}
+
+/// Replaces the fenced code blocks whose language `highlight` knows with highlighted HTML.
+fn highlight_code<'a>(
+    events: Vec<Event<'a>>,
+    theme: Theme,
+    line_numbers: bool,
+    cache: &mut Memo,
+) -> Vec<Event<'a>> {
+    let mut output = Vec::with_capacity(events.len());
+    let mut block: Option<(String, Vec<Event>)> = None;
+    for event in events {
+        match (block.take(), event) {
+            (Some((lang, mut events)), Event::End(Tag::CodeBlock(kind))) => {
+                let code: String = events
+                    .iter()
+                    .filter_map(|event| match event {
+                        Event::Text(text) => Some(&**text),
+                        _ => None,
+                    })
+                    .collect();
+                // Code in no known language is remembered as "" and left as it is.
+                let key = hash_of((&code, &lang, theme, line_numbers));
+                let html = cache.get_or_insert_with(key, || {
+                    highlight(&code, &lang, theme, line_numbers)
+                        .map(|html| mark(&html))
+                        .unwrap_or_default()
+                });
+                match Some(html).filter(|html| !html.is_empty()) {
+                    Some(html) => output.push(Event::Html(html.into())),
+                    None => {
+                        output.append(&mut events);
+                        output.push(Event::End(Tag::CodeBlock(kind)));
+                    }
+                }
+            }
+            (Some((lang, mut events)), event) => {
+                events.push(event);
+                block = Some((lang, events));
+            }
+            (None, Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(info))))
+                if !info.is_empty() =>
+            {
+                let lang = info.split(' ').next().unwrap().to_string();
+                let start = Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(info)));
+                block = Some((lang, vec![start]));
+            }
+            (None, event) => output.push(event),
+        }
+    }
+    output
+}
+
+/// Renders a post body written in Markdown with TeX math, with every extension on and code
+/// highlighted and math macros defined as `config.json` says.
+///
+/// A leading front matter block is left out of the HTML and parsed into
+/// `RenderedPost::front_matter`; macros it declares are used for this post. Math is found on
+/// the source text, so Markdown never sees (and mangles) TeX, while code blocks and code spans
+/// are skipped and stay literal.
+pub fn render(input: String) -> RenderedPost {
+    render_with(input, RenderOptions::configured())
+}
+
+/// Renders `input` for its text, counts and excerpt alone, skipping code highlighting since
+/// the HTML is not shown.
+pub fn summarize(input: String) -> RenderedPost {
+    render_with(
+        input,
+        RenderOptions {
+            highlight: false,
+            ..RenderOptions::configured()
+        },
+    )
+}
+
+/// Renders like `render`, with only the Markdown extensions `options` turns on.
+pub fn render_with(input: String, options: RenderOptions) -> RenderedPost {
+    RenderCache::default().render_with(input, options)
+}
+
+fn hash_of(value: impl Hash) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Strings made from something else, by a hash of it. Those not asked for since the last
+/// `sweep` are dropped by the next one.
+#[derive(Default)]
+struct Memo {
+    kept: HashMap<u64, String>,
+    used: HashMap<u64, String>,
+}
+
+impl Memo {
+    fn get_or_insert_with(&mut self, key: u64, make: impl FnOnce() -> String) -> String {
+        if let Some(value) = self.used.get(&key) {
+            return value.clone();
+        }
+        let value = self.kept.remove(&key).unwrap_or_else(make);
+        self.used.insert(key, value.clone());
+        value
+    }
+
+    fn sweep(&mut self) {
+        self.kept = std::mem::take(&mut self.used);
+    }
+}
+
+/// Renders the same post over and over as it is edited, redoing the slow parts only for what
+/// changed: each formula is typeset by KaTeX and each code block highlighted once, and kept by
+/// a hash of its source for as long as the post still has it. Rendering an unchanged post
+/// again returns the last result as is.
+///
+/// The Markdown itself is parsed whole every time, so footnotes, numbering and references
+/// across blocks come out exactly as `render_with` would have them. Only the HTML of the
+/// top-level blocks that came out different is sanitized again, unless the post has HTML of
+/// its own, admonitions included, which can open in one block and close in another.
+#[derive(Default)]
+pub struct RenderCache {
+    formulas: Memo,
+    code: Memo,
+    blocks: Memo,
+    sanitized: usize,
+    last: Option<(u64, RenderedPost)>,
+}
+
+impl RenderCache {
+    /// Renders like `render`.
+    pub fn render(&mut self, input: String) -> RenderedPost {
+        self.render_with(input, RenderOptions::configured())
+    }
+
+    /// Renders like `render_with`.
+    pub fn render_with(&mut self, input: String, options: RenderOptions) -> RenderedPost {
+        self.sanitized = 0;
+        let key = hash_of((&input, options.key()));
+        match &self.last {
+            Some((last, post)) if *last == key => return post.clone(),
+            _ => {}
+        }
+        let post = self.render_anew(input, options);
+        self.formulas.sweep();
+        self.code.sweep();
+        self.blocks.sweep();
+        self.last = Some((key, post.clone()));
+        post
+    }
+
+    /// How many pieces of HTML the last render sanitized: the blocks that changed, or the whole
+    /// post if it could not be split, and none if the post did not change at all.
+    pub fn sanitized(&self) -> usize {
+        self.sanitized
+    }
+
+    /// Turns `events` split by `BLOCK_END` into sanitized HTML, sanitizing only the blocks
+    /// not kept from the last render.
+    fn render_blocks(&mut self, events: Vec<Event<'_>>) -> String {
+        let mut output = String::new();
+        let mut fresh_line = true;
+        let ends_block = |event: &Event| matches!(event, Event::Html(html) if &**html == BLOCK_END);
+        for block in events.split(ends_block) {
+            // Source line marks change whenever a line is added above them, so they are put in
+            // as they are rather than with the block after them.
+            let marks = block
+                .iter()
+                .take_while(|event| match event {
+                    Event::Html(html) => unmark(html).starts_with(SOURCE_LINE),
+                    _ => false,
+                })
+                .count();
+            for event in &block[..marks] {
+                if let Event::Html(html) = event {
+                    output.push_str(&unmark(html));
+                    fresh_line = html.ends_with('\n');
+                }
+            }
+            // pulldown-cmark starts a block on a line of its own unless the HTML before it
+            // ended one, as a `BLOCK_END` taken out again makes it think.
+            let start = Some(Event::Html(BLOCK_END.into())).filter(|_| !fresh_line);
+            let mut raw = String::new();
+            html::push_html(
+                &mut raw,
+                start.into_iter().chain(block[marks..].iter().cloned()),
+            );
+            let raw = raw.strip_prefix(BLOCK_END).unwrap_or(&raw);
+            if raw.is_empty() {
+                continue;
+            }
+            fresh_line = raw.ends_with('\n');
+            let sanitized = &mut self.sanitized;
+            output.push_str(&self.blocks.get_or_insert_with(hash_of(&raw), || {
+                *sanitized += 1;
+                sanitize(&raw)
+            }));
+        }
+        output
+    }
+
+    fn render_anew(&mut self, input: String, options: RenderOptions) -> RenderedPost {
+        let input = strip_marks(input);
+        let mut warnings = vec![];
+        let (front_matter, body) = front_matter::split(&input);
+        let front_matter = front_matter.unwrap_or_else(|e| {
+            warnings.push(e);
+            FrontMatter::default()
+        });
+        let macros = usable_macros(&options.macros, &front_matter.math_macros, &mut warnings);
+        let parser_options = options.parser_options();
+        let code = code_ranges(body, parser_options);
+        let mut formulas = vec![];
+        let mut math_map = SourceMap::default();
+        let source = extract_math(
+            body,
+            &code,
+            &macros,
+            &mut formulas,
+            &mut self.formulas,
+            &mut math_map,
+        );
+        let (source, labels, admonition_map) = if options.admonitions {
+            admonition::expand(&source, parser_options, &mut warnings)
+        } else {
+            (source, HashMap::new(), SourceMap::default())
+        };
+        let parser = Parser::new_ext(&source, parser_options);
+        let mut has_html = false;
+        let events = if options.source_lines {
+            // The body is what is left of the input after the front matter.
+            let front_matter_len = input.len() - body.len();
+            let newlines: Vec<usize> = input.match_indices('\n').map(|(i, _)| i).collect();
+            let parser = parser
+                .into_offset_iter()
+                .inspect(|(event, _)| has_html |= matches!(event, Event::Html(_)));
+            mark_source_lines(parser, |offset| {
+                let offset = front_matter_len + math_map.map(admonition_map.map(offset));
+                // The line is the number of line breaks before it.
+                match newlines.binary_search(&offset) {
+                    Ok(line) | Err(line) => line,
+                }
+            })
+        } else {
+            parser
+                .inspect(|event| has_html |= matches!(event, Event::Html(_)))
+                .collect()
+        };
+        let events = if has_html { events } else { end_blocks(events) };
+        let mut post_links = vec![];
+        let events = link_refs_in(events, &labels, &mut post_links, &mut warnings);
+        let text = plain_text(&events, &formulas);
+        let (events, toc) = anchor_headings(events, &formulas);
+        let mut events: Vec<Event> = events
+            .into_iter()
+            .flat_map(|event| match event {
+                Event::Text(text) => insert_math(text, &formulas),
+                // Admonition titles, which are written into the Markdown as HTML.
+                Event::Html(html) if html.contains(MATH_START) => vec![Event::Html(
+                    split_math(&html, &formulas)
+                        .into_iter()
+                        .map(|piece| match piece {
+                            Piece::Text(text) => text,
+                            Piece::Math(formula) => &formula.html,
+                        })
+                        .collect::<String>()
+                        .into(),
+                )],
+                event => vec![event],
+            })
+            .collect();
+        if options.footnotes {
+            events = link_footnotes(events);
+        }
+        if options.task_lists {
+            events = mark_task_lists(events);
+        }
+        if options.highlight {
+            events = highlight_code(
+                events,
+                options.code_theme,
+                options.line_numbers,
+                &mut self.code,
+            );
+        }
+        let html = if has_html {
+            let mut html_output: String = String::with_capacity(input.len() * 3 / 2);
+            html::push_html(&mut html_output, events.into_iter());
+            self.sanitized += 1;
+            sanitize(&html_output)
+        } else {
+            self.render_blocks(events)
+        };
+        let words = count_words(&text);
+        RenderedPost {
+            html,
+            chars: text.chars().filter(|c| !c.is_whitespace()).count(),
+            reading_minutes: ((words + WORDS_PER_MINUTE - 1) / WORDS_PER_MINUTE).max(1),
+            excerpt: match &front_matter.summary {
+                Some(summary) => summary.trim().to_string(),
+                None => excerpt(&text, options.excerpt_words),
+            },
+            words,
+            text,
+            toc,
+            front_matter,
+            post_links,
+            warnings,
+        }
     }
-    let mut options = Options::empty();
-    options.insert(Options::ENABLE_STRIKETHROUGH);
-    let parser = Parser::new_ext(&real_output, options);
-    let mut html_output: String = String::with_capacity(input.len() * 3 / 2);
-    html::push_html(&mut html_output, parser);
-    html_output
 }
@@ -28,3 +28,16 @@ impl Default for MainRoute {
         Self::Main
     }
 }
+
+/// The path `route` is found at, such as `/view_post/42`.
+pub fn path(route: MainRoute) -> String {
+    Route::<()>::from(route).route
+}
+
+/// The route found at `path`, if any.
+pub fn route_of(path: &str) -> Option<MainRoute> {
+    MainRoute::switch(Route {
+        route: path.to_string(),
+        state: (),
+    })
+}
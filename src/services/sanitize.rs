@@ -0,0 +1,224 @@
+use super::admonition::KINDS;
+use ammonia::Builder;
+use std::borrow::Cow;
+use std::collections::HashSet;
+
+/// Starts the `id` and `class` values of markup the renderer writes itself, the only ids kept
+/// and classes kept whole. Authors cannot write it: it is a C1 control character, which no
+/// character reference decodes to, and `strip_marks` takes it out of their Markdown.
+const MARK: char = '\u{9F}';
+
+/// Classes of the app's own that only change how a post looks, which HTML in posts may use too.
+/// Those the app picks elements by, such as `post-embed` and `source-line`, are only kept from
+/// markup it wrote.
+const CLASSES: &[&str] = &[
+    "admonition",
+    "admonition-title",
+    "anchor",
+    "contains-task-list",
+    "cross-ref",
+    "footnote-backref",
+    "footnote-definition",
+    "footnote-reference",
+    "math-error",
+    "task-list-item",
+];
+
+/// Tags KaTeX emits for its MathML output, next to the HTML it also renders.
+const MATHML_TAGS: &[&str] = &[
+    "math",
+    "semantics",
+    "annotation",
+    "mrow",
+    "mi",
+    "mn",
+    "mo",
+    "ms",
+    "mtext",
+    "mspace",
+    "msup",
+    "msub",
+    "msubsup",
+    "mfrac",
+    "msqrt",
+    "mroot",
+    "mover",
+    "munder",
+    "munderover",
+    "mtable",
+    "mtr",
+    "mtd",
+    "mstyle",
+    "mpadded",
+    "mphantom",
+    "menclose",
+];
+
+/// SVG used by KaTeX to draw stretchy symbols such as `\sqrt` and `\overrightarrow`.
+const SVG_TAGS: &[&str] = &["svg", "path", "line"];
+
+/// CSS properties KaTeX sets inline. Anything that could move content out of the post
+/// (`position`, `z-index`, ...) or load resources is dropped.
+const STYLE_PROPERTIES: &[&str] = &[
+    "height",
+    "width",
+    "min-width",
+    "vertical-align",
+    "top",
+    "bottom",
+    "margin-left",
+    "margin-right",
+    "margin-top",
+    "margin-bottom",
+    "padding-left",
+    "padding-right",
+    "border-bottom-width",
+    "border-top-width",
+    "border-right-width",
+    "border-left-width",
+    "border-style",
+    "border-color",
+    "color",
+    "background-color",
+];
+
+/// Keeps the declarations of `style` whose property is allowed and whose value is plain
+/// lengths, keywords or colors. No parentheses means no `url(..)` or `expression(..)`.
+fn filter_style(style: &str) -> Option<String> {
+    let kept: Vec<String> = style
+        .split(';')
+        .filter_map(|declaration| {
+            let mut parts = declaration.splitn(2, ':');
+            let property = parts.next()?.trim().to_ascii_lowercase();
+            let value = parts.next()?.trim();
+            let plain = value
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || " .-%#,".contains(c));
+            if STYLE_PROPERTIES.contains(&property.as_str()) && plain && !value.is_empty() {
+                Some(format!("{}:{}", property, value))
+            } else {
+                None
+            }
+        })
+        .collect();
+    if kept.is_empty() {
+        None
+    } else {
+        Some(kept.join(";"))
+    }
+}
+
+/// Keeps the classes of `class` that are in `CLASSES`, name an admonition kind or, as
+/// pulldown-cmark writes for code blocks, a language.
+fn filter_class(class: &str) -> Option<String> {
+    let kept: Vec<&str> = class
+        .split_ascii_whitespace()
+        .filter(|class| {
+            CLASSES.contains(class)
+                || class.starts_with("language-")
+                || class
+                    .strip_prefix("admonition-")
+                    .map_or(false, |kind| KINDS.iter().any(|known| known.name == kind))
+        })
+        .collect();
+    if kept.is_empty() {
+        None
+    } else {
+        Some(kept.join(" "))
+    }
+}
+
+fn builder() -> Builder<'static> {
+    let mut builder = Builder::default();
+    builder
+        .add_tags(MATHML_TAGS)
+        .add_tags(SVG_TAGS)
+        .add_tags(&["input", "section"])
+        .add_generic_attributes(&["class", "style", "aria-hidden"])
+        // Task list checkboxes are the only inputs; whatever else is written becomes one.
+        .add_tag_attributes("input", &["checked"])
+        .set_tag_attribute_value("input", "type", "checkbox")
+        .set_tag_attribute_value("input", "disabled", "")
+        .add_tag_attributes("sup", &["id"])
+        // Marks the editor keeps its panes scrolled together by.
+        .add_tag_attributes("span", &["data-source-line"])
+        .add_tag_attributes("div", &["id"])
+        // Admonitions; numbered ones are linked to by id.
+        .add_tag_attributes("aside", &["id"])
+        .set_tag_attribute_value("aside", "role", "note")
+        .add_tag_attributes("section", &["id"])
+        .add_tag_attributes("h1", &["id"])
+        .add_tag_attributes("h2", &["id"])
+        .add_tag_attributes("h3", &["id"])
+        .add_tag_attributes("h4", &["id"])
+        .add_tag_attributes("h5", &["id"])
+        .add_tag_attributes("h6", &["id"])
+        .add_tag_attributes("annotation", &["encoding"])
+        .add_tag_attributes("math", &["xmlns", "display"])
+        .add_tag_attributes(
+            "mo",
+            &["fence", "stretchy", "separator", "lspace", "rspace"],
+        )
+        .add_tag_attributes("mspace", &["width"])
+        .add_tag_attributes("mstyle", &["mathcolor", "displaystyle", "scriptlevel"])
+        .add_tag_attributes("mtable", &["rowspacing", "columnspacing", "columnalign"])
+        .add_tag_attributes(
+            "mpadded",
+            &["width", "height", "depth", "lspace", "voffset"],
+        )
+        .add_tag_attributes("menclose", &["notation"])
+        .add_tag_attributes(
+            "svg",
+            &["xmlns", "width", "height", "viewBox", "preserveAspectRatio"],
+        )
+        .add_tag_attributes("path", &["d"])
+        .add_tag_attributes("line", &["x1", "y1", "x2", "y2", "stroke-width"])
+        .url_schemes(
+            ["http", "https", "mailto"]
+                .iter()
+                .copied()
+                .collect::<HashSet<_>>(),
+        )
+        .attribute_filter(|_element, attribute, value| match attribute {
+            "style" => filter_style(value).map(Cow::Owned),
+            "id" => value
+                .strip_prefix(MARK)
+                .map(|id| Cow::Owned(id.to_string())),
+            "class" => match value.strip_prefix(MARK) {
+                Some(class) => Some(Cow::Owned(class.to_string())),
+                None => filter_class(value).map(Cow::Owned),
+            },
+            _ => Some(Cow::Borrowed(value)),
+        });
+    builder
+}
+
+/// Strips everything but an allow-list of tags, attributes and URL schemes from rendered
+/// post HTML, keeping the markup KaTeX generates, heading and footnote anchors, task list
+/// checkboxes and admonitions. Ids and classes are only kept whole where `mark` put them.
+pub fn sanitize(html: &str) -> String {
+    builder().clean(html).to_string()
+}
+
+/// Marks the `id` and `class` values of `html`, which the renderer wrote itself, to be kept by
+/// `sanitize`. Any text put in it must be escaped, so that only its own attributes are marked.
+pub fn mark(html: &str) -> String {
+    html.replace(" id=\"", &format!(" id=\"{}", MARK))
+        .replace(" class=\"", &format!(" class=\"{}", MARK))
+}
+
+/// Takes the marks of `mark` out of `html`, for markup the renderer wrote that needs no
+/// sanitizing.
+pub fn unmark(html: &str) -> String {
+    html.replace(MARK, "")
+}
+
+/// Replaces the marks of `mark` in Markdown to render, keeping every offset in it the same.
+pub fn strip_marks(markdown: String) -> String {
+    if markdown.contains(MARK) {
+        let blank: String = std::iter::repeat(' ').take(MARK.len_utf8()).collect();
+        markdown.replace(MARK, &blank)
+    } else {
+        markdown
+    }
+}
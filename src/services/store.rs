@@ -0,0 +1,145 @@
+use crate::api::cache;
+use crate::api::*;
+use crate::services::token;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+use wasm_bindgen_futures::spawn_local;
+use yew::agent::{Agent, AgentLink, Context, HandlerId};
+
+/// Application-wide state shared by every component bridged to `AppStore`.
+#[derive(Clone, Default)]
+pub struct AppState {
+    /// `None` while logged out, or until `loaded` is set.
+    pub user: Option<InfoResponse>,
+    pub token: Option<String>,
+    /// Posts fetched so far in this session, by id.
+    pub posts: HashMap<i64, PublicPost>,
+    /// Whether the first user lookup has finished.
+    pub loaded: bool,
+}
+
+impl AppState {
+    pub fn is_logined(&self) -> bool {
+        self.user.is_some()
+    }
+
+    pub fn is_admin(&self) -> bool {
+        self.user
+            .as_ref()
+            .map_or(false, |user| user.level == AccountLevel::Admin)
+    }
+}
+
+pub enum Request {
+    /// Re-fetch the current user with the stored token.
+    Refresh,
+    Login {
+        token: String,
+        remember: bool,
+    },
+    Logout,
+    CachePost(i64, PublicPost),
+    ForgetPost(i64),
+}
+
+pub enum Msg {
+    ReceiveInfo(Result<ResponseBlock<InfoResponse>, ApiError>),
+}
+
+/// Holds the logged-in user, token and cached posts.
+///
+/// Components subscribe with `AppStore::bridge` and get the whole `AppState` on connect and
+/// after every change, so login and logout update every view at once.
+pub struct AppStore {
+    link: AgentLink<Self>,
+    subscribers: HashSet<HandlerId>,
+    state: Rc<AppState>,
+}
+
+impl Agent for AppStore {
+    type Reach = Context<Self>;
+    type Message = Msg;
+    type Input = Request;
+    type Output = Rc<AppState>;
+
+    fn create(link: AgentLink<Self>) -> Self {
+        let mut store = Self {
+            link,
+            subscribers: HashSet::new(),
+            state: Rc::new(AppState::default()),
+        };
+        store.fetch_info();
+        store
+    }
+
+    fn update(&mut self, msg: Self::Message) {
+        match msg {
+            Msg::ReceiveInfo(resp) => {
+                let state = Rc::make_mut(&mut self.state);
+                state.user = match resp {
+                    Ok(block) if block.status => block.body,
+                    _ => None,
+                };
+                if state.user.is_none() {
+                    state.token = None;
+                }
+                state.loaded = true;
+                self.broadcast();
+            }
+        }
+    }
+
+    fn connected(&mut self, id: HandlerId) {
+        self.subscribers.insert(id);
+        self.link.respond(id, self.state.clone());
+    }
+
+    fn handle_input(&mut self, msg: Self::Input, _who: HandlerId) {
+        match msg {
+            Request::Refresh => self.fetch_info(),
+            Request::Login { token, remember } => {
+                token::save(&token, remember);
+                cache::clear();
+                self.fetch_info();
+            }
+            Request::Logout => {
+                token::clear();
+                cache::clear();
+                let state = Rc::make_mut(&mut self.state);
+                state.user = None;
+                state.token = None;
+                self.broadcast();
+            }
+            Request::CachePost(id, post) => {
+                Rc::make_mut(&mut self.state).posts.insert(id, post);
+                self.broadcast();
+            }
+            Request::ForgetPost(id) => {
+                if Rc::make_mut(&mut self.state).posts.remove(&id).is_some() {
+                    self.broadcast();
+                }
+            }
+        }
+    }
+
+    fn disconnected(&mut self, id: HandlerId) {
+        self.subscribers.remove(&id);
+    }
+}
+
+impl AppStore {
+    fn fetch_info(&mut self) {
+        let client = ApiClient::new();
+        Rc::make_mut(&mut self.state).token = token::load_valid();
+        let link = self.link.clone();
+        spawn_local(async move {
+            link.send_message(Msg::ReceiveInfo(client.get_info().await));
+        });
+    }
+
+    fn broadcast(&self) {
+        for id in self.subscribers.iter() {
+            self.link.respond(*id, self.state.clone());
+        }
+    }
+}
@@ -0,0 +1,210 @@
+use crate::services::cookie::{CookieOptions, CookieService};
+use crate::services::jwt;
+use std::cell::{Cell, RefCell};
+
+const TOKEN_KEY: &str = "token";
+const REMEMBER_DAYS: i64 = 30;
+
+/// Somewhere the JWT can be kept between requests.
+pub trait TokenStore {
+    fn load(&self) -> Option<String>;
+    fn save(&self, token: &str);
+    fn clear(&self);
+}
+
+pub struct CookieStore {
+    options: CookieOptions,
+}
+
+impl CookieStore {
+    pub fn new(options: CookieOptions) -> Self {
+        Self { options }
+    }
+}
+
+impl TokenStore for CookieStore {
+    fn load(&self) -> Option<String> {
+        CookieService::new()
+            .get(TOKEN_KEY)
+            .ok()
+            .filter(|token| !token.is_empty())
+    }
+
+    fn save(&self, token: &str) {
+        CookieService::new().set_with(TOKEN_KEY, token, &self.options);
+    }
+
+    fn clear(&self) {
+        CookieService::new().remove_with(TOKEN_KEY, &self.options);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum StorageArea {
+    Local,
+    Session,
+}
+
+/// `localStorage` or `sessionStorage`.
+pub struct WebStorageStore {
+    area: StorageArea,
+}
+
+impl WebStorageStore {
+    pub fn new(area: StorageArea) -> Self {
+        Self { area }
+    }
+
+    fn storage(&self) -> Option<web_sys::Storage> {
+        let window = web_sys::window()?;
+        let storage = match self.area {
+            StorageArea::Local => window.local_storage(),
+            StorageArea::Session => window.session_storage(),
+        };
+        storage.ok().flatten()
+    }
+}
+
+impl TokenStore for WebStorageStore {
+    fn load(&self) -> Option<String> {
+        self.storage()?.get_item(TOKEN_KEY).ok().flatten()
+    }
+
+    fn save(&self, token: &str) {
+        if let Some(storage) = self.storage() {
+            storage.set_item(TOKEN_KEY, token).ok();
+        }
+    }
+
+    fn clear(&self) {
+        if let Some(storage) = self.storage() {
+            storage.remove_item(TOKEN_KEY).ok();
+        }
+    }
+}
+
+thread_local! {
+    static MEMORY_TOKEN: RefCell<Option<String>> = RefCell::new(None);
+}
+
+/// Lives only as long as the page; every `MemoryStore` shares the same slot.
+pub struct MemoryStore;
+
+impl TokenStore for MemoryStore {
+    fn load(&self) -> Option<String> {
+        MEMORY_TOKEN.with(|token| token.borrow().clone())
+    }
+
+    fn save(&self, token: &str) {
+        MEMORY_TOKEN.with(|slot| *slot.borrow_mut() = Some(token.to_string()));
+    }
+
+    fn clear(&self) {
+        MEMORY_TOKEN.with(|slot| *slot.borrow_mut() = None);
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backend {
+    Cookie,
+    LocalStorage,
+    SessionStorage,
+    Memory,
+}
+
+impl Backend {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "cookie" => Some(Backend::Cookie),
+            "local" | "localstorage" => Some(Backend::LocalStorage),
+            "session" | "sessionstorage" => Some(Backend::SessionStorage),
+            "memory" => Some(Backend::Memory),
+            _ => None,
+        }
+    }
+
+    /// Store for a login with or without "remember me".
+    ///
+    /// A remembered login outlives the browser session where the backend allows it;
+    /// otherwise the token is dropped once the tab or browser is closed.
+    pub fn store(self, remember: bool) -> Box<dyn TokenStore> {
+        match (self, remember) {
+            (Backend::Cookie, true) => Box::new(CookieStore::new(
+                CookieOptions::default().expires_in(chrono::Duration::days(REMEMBER_DAYS)),
+            )),
+            (Backend::Cookie, false) => {
+                Box::new(CookieStore::new(CookieOptions::default().session()))
+            }
+            (Backend::LocalStorage, true) => Box::new(WebStorageStore::new(StorageArea::Local)),
+            (Backend::LocalStorage, false) | (Backend::SessionStorage, _) => {
+                Box::new(WebStorageStore::new(StorageArea::Session))
+            }
+            (Backend::Memory, _) => Box::new(MemoryStore),
+        }
+    }
+}
+
+impl Default for Backend {
+    /// Picked at build time from `BLOG_TOKEN_STORE`, falling back to cookies.
+    fn default() -> Self {
+        option_env!("BLOG_TOKEN_STORE")
+            .and_then(Backend::from_name)
+            .unwrap_or(Backend::Cookie)
+    }
+}
+
+thread_local! {
+    static BACKEND: Cell<Backend> = Cell::new(Backend::default());
+    static REMEMBERED: Cell<Option<bool>> = Cell::new(None);
+}
+
+pub fn backend() -> Backend {
+    BACKEND.with(|backend| backend.get())
+}
+
+/// Switches the backend at runtime. Tokens saved in the previous backend are not migrated.
+pub fn set_backend(backend: Backend) {
+    BACKEND.with(|b| b.set(backend));
+}
+
+pub fn load() -> Option<String> {
+    let backend = backend();
+    backend
+        .store(true)
+        .load()
+        .or_else(|| backend.store(false).load())
+}
+
+/// Like `load`, but drops a token whose `exp` has already passed.
+pub fn load_valid() -> Option<String> {
+    let token = load()?;
+    if jwt::is_expired(&token) {
+        clear();
+        None
+    } else {
+        Some(token)
+    }
+}
+
+pub fn save(token: &str, remember: bool) {
+    let backend = backend();
+    backend.store(!remember).clear();
+    backend.store(remember).save(token);
+    REMEMBERED.with(|r| r.set(Some(remember)));
+}
+
+/// Swaps in a refreshed token, keeping it wherever the current one lives.
+pub fn replace(token: &str) {
+    let backend = backend();
+    // Cookies look the same either way, so without a record from `save` assume a session login.
+    let remember = REMEMBERED
+        .with(|r| r.get())
+        .unwrap_or_else(|| backend != Backend::Cookie && backend.store(true).load().is_some());
+    save(token, remember);
+}
+
+pub fn clear() {
+    let backend = backend();
+    backend.store(true).clear();
+    backend.store(false).clear();
+}
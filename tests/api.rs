@@ -0,0 +1,266 @@
+//! `ApiClient` against the mock backend: auth, token refresh, caching and error mapping.
+
+mod support;
+
+use blog_frontend::api::*;
+use blog_frontend::services::token;
+use futures::future::join;
+use gloo_timers::future::TimeoutFuture;
+use support::{MockBackend, ADMIN, GUEST};
+use wasm_bindgen_test::*;
+
+wasm_bindgen_test_configure!(run_in_browser);
+
+fn form(title: &str, body: &str) -> NewPostForm {
+    NewPostForm {
+        title: title.to_string(),
+        body: body.to_string(),
+        tag: vec!["test".to_string()],
+    }
+}
+
+fn blog_error<T>(resp: &ResponseBlock<T>, error: impl Fn(&T) -> BlogError) -> BlogError {
+    error(resp.body.as_ref().expect("empty body"))
+}
+
+async fn fetch_post(client: &ApiClient, id: i64) -> PublicPost {
+    let resp = client.view_post(id).await.unwrap();
+    resp.body
+        .and_then(|body| body.post)
+        .expect("post not found")
+}
+
+#[wasm_bindgen_test]
+async fn login_reports_account_errors() {
+    let _backend = MockBackend::install();
+    let client = ApiClient::new();
+
+    let unknown = LoginForm {
+        username: "nobody".to_string(),
+        pass: "x".to_string(),
+    };
+    let resp = client.login(unknown).await.unwrap();
+    assert_eq!(resp.body.unwrap().result, AccountError::UserNotExists);
+
+    let wrong = LoginForm {
+        username: ADMIN.0.to_string(),
+        pass: "wrong".to_string(),
+    };
+    let resp = client.login(wrong).await.unwrap();
+    assert_eq!(resp.body.unwrap().result, AccountError::PassNotMatched);
+}
+
+#[wasm_bindgen_test]
+async fn post_lifecycle() {
+    let backend = MockBackend::install();
+    token::save(&backend.token_for(ADMIN.0), false);
+    let client = ApiClient::new();
+
+    let resp = client.new_post(form("Hello", "First post")).await.unwrap();
+    assert_eq!(blog_error(&resp, |b| b.error), BlogError::Nothing);
+    let id = client.recent_posts().await.unwrap().body.unwrap().posts[0];
+
+    let post = fetch_post(&client, id).await;
+    assert_eq!(post.title, "Hello");
+    assert_eq!(post.tags, vec!["test".to_string()]);
+
+    let edit = EditPostForm {
+        pk: id,
+        title: "Hello again".to_string(),
+        body: post.body,
+        tag: post.tags,
+    };
+    let resp = client.edit_post(edit).await.unwrap();
+    assert_eq!(blog_error(&resp, |b| b.error), BlogError::Nothing);
+    let post = fetch_post(&client, id).await;
+    assert_eq!(post.title, "Hello again");
+
+    let resp = client.delete_post(id).await.unwrap();
+    assert_eq!(blog_error(&resp, |b| b.error), BlogError::Nothing);
+    let resp = client.view_post(id).await.unwrap();
+    assert!(resp.body.unwrap().post.is_none());
+    assert!(backend.post_ids().is_empty());
+}
+
+#[wasm_bindgen_test]
+async fn writes_need_a_token_and_permission() {
+    let backend = MockBackend::install();
+
+    let err = ApiClient::new().new_post(form("t", "b")).await.err();
+    assert_eq!(err, Some(ApiError::Unauthorized));
+    assert_eq!(backend.calls("/api/blog/new_post"), 0);
+
+    token::save(&backend.token_for(GUEST.0), false);
+    let resp = ApiClient::new().new_post(form("t", "b")).await.unwrap();
+    assert_eq!(blog_error(&resp, |b| b.error), BlogError::PermissionError);
+
+    token::save(&backend.token_for(ADMIN.0), false);
+    let resp = ApiClient::new().new_post(form("", "b")).await.unwrap();
+    assert_eq!(blog_error(&resp, |b| b.error), BlogError::TooShortTitle);
+}
+
+#[wasm_bindgen_test]
+async fn rejected_token_is_refreshed_and_request_replayed() {
+    let backend = MockBackend::install();
+    let stale = backend.token_for(ADMIN.0);
+    token::save(&stale, false);
+    backend.expire_sessions();
+
+    let resp = ApiClient::new().new_post(form("t", "b")).await.unwrap();
+    assert_eq!(blog_error(&resp, |b| b.error), BlogError::Nothing);
+    assert_eq!(backend.calls("/api/account_service/refresh"), 1);
+    assert_eq!(backend.calls("/api/blog/new_post"), 2);
+    let fresh = token::load().unwrap();
+    assert_ne!(fresh, stale);
+
+    // A 401 from the account service takes the same path.
+    backend.expire_sessions();
+    let info = ApiClient::new().get_info().await.unwrap();
+    assert_eq!(info.body.unwrap().username, ADMIN.0);
+    assert_eq!(backend.calls("/api/account_service/refresh"), 2);
+}
+
+#[wasm_bindgen_test]
+async fn concurrent_rejections_share_one_refresh() {
+    let backend = MockBackend::install();
+    backend.seed_posts(2);
+    token::save(&backend.token_for(ADMIN.0), false);
+    backend.expire_sessions();
+
+    let client = ApiClient::new();
+    let (a, b) = join(client.delete_post(1), client.delete_post(2)).await;
+    assert_eq!(blog_error(&a.unwrap(), |b| b.error), BlogError::Nothing);
+    assert_eq!(blog_error(&b.unwrap(), |b| b.error), BlogError::Nothing);
+    assert_eq!(backend.calls("/api/account_service/refresh"), 1);
+}
+
+#[wasm_bindgen_test]
+async fn rejection_after_a_refresh_replays_with_the_new_token() {
+    let backend = MockBackend::install();
+    token::save(&backend.token_for(ADMIN.0), false);
+    let client = ApiClient::new();
+    // Another request refreshed the token while this client still holds the old one.
+    backend.expire_sessions();
+    token::replace(&backend.token_for(ADMIN.0));
+
+    let resp = client.new_post(form("t", "b")).await.unwrap();
+    assert_eq!(blog_error(&resp, |b| b.error), BlogError::Nothing);
+    assert_eq!(backend.calls("/api/account_service/refresh"), 0);
+    assert_eq!(backend.calls("/api/blog/new_post"), 2);
+}
+
+#[wasm_bindgen_test]
+async fn rejected_replay_logs_out() {
+    let backend = MockBackend::install();
+    token::save(&backend.token_for(ADMIN.0), false);
+    let client = ApiClient::new();
+    token::replace(&backend.token_for(ADMIN.0));
+    backend.fail_with(Some(401));
+
+    let err = client.new_post(form("t", "b")).await.err();
+    assert_eq!(err, Some(ApiError::Unauthorized));
+    assert_eq!(backend.calls("/api/blog/new_post"), 2);
+    assert_eq!(token::load(), None);
+}
+
+#[wasm_bindgen_test]
+async fn failed_refresh_logs_out() {
+    let backend = MockBackend::install();
+    token::save(&backend.token_for(ADMIN.0), false);
+    backend.revoke_tokens();
+
+    let err = ApiClient::new().new_post(form("t", "b")).await.err();
+    assert_eq!(err, Some(ApiError::Account(AccountError::UserNotExists)));
+    assert_eq!(token::load(), None);
+}
+
+#[wasm_bindgen_test]
+async fn reads_go_out_without_a_login_when_a_refresh_fails() {
+    let backend = MockBackend::install();
+    backend.seed_posts(1);
+    token::save(&backend.token_for(ADMIN.0), false);
+    backend.revoke_tokens();
+    backend.delay("/api/account_service/refresh", 50);
+    let client = ApiClient::new();
+
+    let read_while_refreshing = async {
+        while backend.calls("/api/account_service/refresh") == 0 {
+            TimeoutFuture::new(5).await;
+        }
+        fetch_post(&client, 1).await
+    };
+    let (write, read) = join(client.new_post(form("t", "b")), read_while_refreshing).await;
+    assert_eq!(
+        write.err(),
+        Some(ApiError::Account(AccountError::UserNotExists))
+    );
+    assert_eq!(read.title, "Post 1");
+    assert_eq!(backend.calls("/api/account_service/refresh"), 1);
+    assert_eq!(token::load(), None);
+}
+
+#[wasm_bindgen_test]
+async fn views_are_cached_and_shared() {
+    let backend = MockBackend::install();
+    backend.seed_posts(1);
+    let client = ApiClient::new();
+
+    let (a, b) = join(client.view_post(1), client.view_post(1)).await;
+    assert!(a.is_ok() && b.is_ok());
+    client.view_post(1).await.unwrap();
+    assert_eq!(backend.calls("/api/blog/view_post"), 1);
+
+    token::save(&backend.token_for(ADMIN.0), false);
+    let edit = EditPostForm {
+        pk: 1,
+        title: "Changed".to_string(),
+        body: "Body".to_string(),
+        tag: vec![],
+    };
+    ApiClient::new().edit_post(edit).await.unwrap();
+    let post = fetch_post(&client, 1).await;
+    assert_eq!(post.title, "Changed");
+    assert_eq!(backend.calls("/api/blog/view_post"), 2);
+}
+
+#[wasm_bindgen_test]
+async fn stale_views_do_not_outlive_an_edit() {
+    let backend = MockBackend::install();
+    backend.seed_posts(1);
+    token::save(&backend.token_for(ADMIN.0), false);
+    backend.delay("/api/blog/view_post", 50);
+    let client = ApiClient::new();
+
+    // The first view is answered with the old title only after the edit and a second view.
+    let edit = EditPostForm {
+        pk: 1,
+        title: "Changed".to_string(),
+        body: "Body".to_string(),
+        tag: vec![],
+    };
+    let edit_then_view = async {
+        client.edit_post(edit).await.unwrap();
+        fetch_post(&client, 1).await
+    };
+    let (first, second) = join(fetch_post(&client, 1), edit_then_view).await;
+    assert_eq!(first.title, "Post 1");
+    assert_eq!(second.title, "Changed");
+
+    let cached = fetch_post(&client, 1).await;
+    assert_eq!(cached.title, "Changed");
+    assert_eq!(backend.calls("/api/blog/view_post"), 2);
+}
+
+#[wasm_bindgen_test]
+async fn transport_failures_map_to_api_errors() {
+    let backend = MockBackend::install();
+
+    backend.fail_with(Some(500));
+    let err = ApiClient::new().get_post_counts().await.err();
+    assert_eq!(err, Some(ApiError::Status(500)));
+
+    backend.fail_with(None);
+    backend.set_offline(true);
+    let err = ApiClient::new().get_post_counts().await.err();
+    assert!(matches!(err, Some(ApiError::Network(_))));
+}
@@ -0,0 +1,25 @@
+use blog_frontend::config::Config;
+use chrono_tz::Tz;
+use wasm_bindgen_test::*;
+
+wasm_bindgen_test_configure!(run_in_browser);
+
+#[wasm_bindgen_test]
+fn missing_fields_keep_defaults() {
+    let config: Config = serde_json::from_str(
+        r#"{ "api_base_url": "https://api.example.com", "posts_per_page": 20 }"#,
+    )
+    .unwrap();
+    assert_eq!(config.api_base_url, "https://api.example.com");
+    assert_eq!(config.posts_per_page, 20);
+    assert_eq!(config.site_title, Config::default().site_title);
+    assert_eq!(config.recent_posts, Config::default().recent_posts);
+}
+
+#[wasm_bindgen_test]
+fn timezone_falls_back_to_utc() {
+    let config: Config = serde_json::from_str(r#"{ "timezone": "Europe/Paris" }"#).unwrap();
+    assert_eq!(config.timezone(), Tz::Europe__Paris);
+    let config: Config = serde_json::from_str(r#"{ "timezone": "Mars/Olympus" }"#).unwrap();
+    assert_eq!(config.timezone(), Tz::UTC);
+}
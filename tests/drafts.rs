@@ -0,0 +1,49 @@
+//! Drafts the editor keeps in `localStorage`.
+
+use blog_frontend::services::drafts::{self, Draft};
+use wasm_bindgen_test::*;
+
+wasm_bindgen_test_configure!(run_in_browser);
+
+fn draft(title: &str) -> Draft {
+    Draft::new(title.to_string(), "Body".to_string(), "rust".to_string())
+}
+
+#[wasm_bindgen_test]
+fn drafts_are_kept_by_post() {
+    drafts::clear();
+    assert_eq!(drafts::load(-1), None);
+
+    drafts::save(-1, &draft("New"));
+    drafts::save(7, &draft("Seventh"));
+    assert_eq!(drafts::load(-1).unwrap().title, "New");
+    assert_eq!(drafts::load(7).unwrap().title, "Seventh");
+    let mut ids: Vec<i64> = drafts::list().into_iter().map(|(id, _)| id).collect();
+    ids.sort_unstable();
+    assert_eq!(ids, vec![-1, 7]);
+
+    drafts::remove(7);
+    assert_eq!(drafts::load(7), None);
+    // An empty draft replaces the one before by removing it.
+    drafts::save(
+        -1,
+        &Draft::new(String::new(), " ".to_string(), String::new()),
+    );
+    assert_eq!(drafts::load(-1), None);
+    assert!(drafts::list().is_empty());
+}
+
+#[wasm_bindgen_test]
+fn other_storage_is_left_alone() {
+    drafts::clear();
+    let storage = web_sys::window().unwrap().local_storage().unwrap().unwrap();
+    storage.set_item("draft:nonsense", "{}").unwrap();
+    storage.set_item("theme", "dark").unwrap();
+    drafts::save(3, &draft("Third"));
+
+    assert_eq!(drafts::list().len(), 1);
+    drafts::clear();
+    assert_eq!(storage.get_item("theme").unwrap().as_deref(), Some("dark"));
+    storage.remove_item("draft:nonsense").unwrap();
+    storage.remove_item("theme").unwrap();
+}
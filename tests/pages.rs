@@ -0,0 +1,423 @@
+//! Pages mounted against the mock backend, driven through their messages and the DOM.
+
+mod support;
+
+use blog_frontend::api::{ApiClient, EditPostForm};
+use blog_frontend::config::Config;
+use blog_frontend::constants::SPLIT_EDITOR_MIN_WIDTH;
+use blog_frontend::pages::{dashboard, editor, list_posts, login, view_post};
+use blog_frontend::services::drafts::{self, Draft};
+use support::{
+    create_post, mount, set_config, sign_in, type_into, wait_for, wait_for_text, MockBackend, ADMIN,
+};
+use wasm_bindgen::JsCast;
+use wasm_bindgen_test::*;
+use web_sys::{Element, HtmlElement};
+
+wasm_bindgen_test_configure!(run_in_browser);
+
+fn click(root: &Element, selector: &str) {
+    root.query_selector(selector)
+        .unwrap()
+        .unwrap_or_else(|| panic!("nothing matches {:?}", selector))
+        .unchecked_into::<HtmlElement>()
+        .click();
+}
+
+/// Whether a list item titled exactly `title` is rendered.
+fn shows_title(root: &Element, title: &str) -> bool {
+    root.inner_html().contains(&format!(">{}<", title))
+}
+
+#[wasm_bindgen_test]
+async fn login_editor_view_delete() {
+    let backend = MockBackend::install();
+    let _store = sign_in(ADMIN).await;
+
+    let (root, link) = mount::<editor::EditorPage>(editor::Props { id: -1 });
+    wait_for_text(&root, "New post to blog").await;
+    type_into(&root, "mwc-textfield[label='Title']", "Flow post");
+    type_into(&root, "mwc-textarea[label='Body']", "Hello *world*");
+    type_into(&root, "mwc-textfield[label='Tags']", "rust, test");
+    link.send_message(editor::Msg::GetNewPost);
+    wait_for("the post to be created", || backend.post_ids().len() == 1).await;
+    let id = backend.post_ids()[0];
+    root.remove();
+
+    let (root, link) = mount::<editor::EditorPage>(editor::Props { id });
+    link.send_message(editor::Msg::SetCur(1));
+    wait_for_text(&root, "Hello <em>world</em>").await;
+    type_into(&root, "mwc-textfield[label='Title']", "Flow post, edited");
+    link.send_message(editor::Msg::GetEditPost);
+    wait_for("the post to be edited", || {
+        backend.post_title(id).as_deref() == Some("Flow post, edited")
+    })
+    .await;
+    root.remove();
+
+    let (root, link) = mount::<view_post::ViewPost>(view_post::Props { id });
+    wait_for_text(&root, "Flow post, edited").await;
+    wait_for_text(&root, "#RUST, #TEST").await;
+    wait_for_text(&root, "DELETE").await;
+    link.send_message(view_post::Msg::GetDelete);
+    wait_for("the post to be deleted", || backend.post_ids().is_empty()).await;
+    root.remove();
+
+    let (root, _) = mount::<view_post::ViewPost>(view_post::Props { id });
+    wait_for_text(&root, "Not found.").await;
+    root.remove();
+}
+
+#[wasm_bindgen_test]
+async fn editor_preview_replaces_only_changed_blocks() {
+    let _backend = MockBackend::install();
+    let _store = sign_in(ADMIN).await;
+    let id = create_post("Blocks", "First block\n\nSecond block", &["rust"]).await;
+
+    let (root, link) = mount::<editor::EditorPage>(editor::Props { id });
+    link.send_message(editor::Msg::SetCur(1));
+    wait_for_text(&root, "Second block").await;
+    let first = root.query_selector(".markdown-body > p").unwrap().unwrap();
+
+    let form = EditPostForm {
+        pk: id,
+        title: "Blocks".to_string(),
+        body: "First block\n\nSecond block, edited".to_string(),
+        tag: vec!["rust".to_string()],
+    };
+    ApiClient::new().edit_post(form).await.unwrap();
+    link.send_message(editor::Msg::GetViewPost);
+    wait_for_text(&root, "Second block, edited").await;
+    let blocks = root.query_selector_all(".markdown-body > p").unwrap();
+    assert_eq!(blocks.length(), 2);
+    assert!(blocks.item(0).unwrap().is_same_node(Some(first.as_ref())));
+    root.remove();
+}
+
+#[wasm_bindgen_test]
+async fn editor_shows_source_and_preview_side_by_side() {
+    let _backend = MockBackend::install();
+    let _store = sign_in(ADMIN).await;
+    let id = create_post("Split", "# Heading\n\nFirst block", &["rust"]).await;
+
+    let (root, link) = mount::<editor::EditorPage>(editor::Props { id });
+    link.send_message(editor::Msg::SetCur(2));
+    let width = web_sys::window().unwrap().inner_width().unwrap();
+    if width.as_f64().unwrap() < SPLIT_EDITOR_MIN_WIDTH {
+        // Too narrow to split, so it stays on editing.
+        wait_for("the textarea", || {
+            root.query_selector("mwc-textarea").unwrap().is_some()
+        })
+        .await;
+        assert!(root.query_selector(".editor-split").unwrap().is_none());
+        root.remove();
+        return;
+    }
+
+    wait_for_text(&root, "First block").await;
+    let preview = root.query_selector(".split-preview").unwrap().unwrap();
+    assert!(preview
+        .query_selector(".source-line[data-source-line=\"2\"] + p")
+        .unwrap()
+        .is_some());
+    type_into(
+        &root,
+        ".split-source",
+        "# Heading\n\nFirst block\n\nTyped block",
+    );
+    wait_for("the preview to follow the typing", || {
+        preview.inner_html().contains("<p>Typed block</p>")
+    })
+    .await;
+    root.remove();
+}
+
+#[wasm_bindgen_test]
+async fn editor_offers_to_restore_a_draft() {
+    let _backend = MockBackend::install();
+    let _store = sign_in(ADMIN).await;
+    let draft = Draft::new(
+        "Lost title".to_string(),
+        "Lost *body*".to_string(),
+        "rust".to_string(),
+    );
+    drafts::save(-1, &draft);
+
+    let (root, link) = mount::<editor::EditorPage>(editor::Props { id: -1 });
+    wait_for_text(&root, "You have a draft of this post").await;
+    link.send_message(editor::Msg::RestoreDraft);
+    link.send_message(editor::Msg::SetCur(1));
+    wait_for_text(&root, "Lost <em>body</em>").await;
+    assert!(!root.inner_html().contains("You have a draft"));
+    root.remove();
+
+    let (root, link) = mount::<editor::EditorPage>(editor::Props { id: -1 });
+    wait_for_text(&root, "You have a draft of this post").await;
+    link.send_message(editor::Msg::DiscardDraft);
+    wait_for("the offer to go away", || {
+        !root.inner_html().contains("You have a draft")
+    })
+    .await;
+    assert_eq!(drafts::load(-1), None);
+    root.remove();
+}
+
+#[wasm_bindgen_test]
+async fn editor_confirms_leaving_with_unsaved_changes() {
+    let _backend = MockBackend::install();
+    let _store = sign_in(ADMIN).await;
+    let id = create_post("Guarded", "Saved body", &["rust"]).await;
+    let draft = Draft::new(
+        "Guarded".to_string(),
+        "Unsaved body".to_string(),
+        "rust".to_string(),
+    );
+    drafts::save(id, &draft);
+    let pathname = || web_sys::window().unwrap().location().pathname().unwrap();
+    let here = pathname();
+    let history = web_sys::window().unwrap().history().unwrap();
+    let entries = history.length().unwrap();
+
+    let (root, link) = mount::<editor::EditorPage>(editor::Props { id });
+    wait_for_text(&root, "You have a draft of this post").await;
+    link.send_message(editor::Msg::RestoreDraft);
+    wait_for("the draft to be restored", || {
+        !root.inner_html().contains("You have a draft")
+    })
+    .await;
+    let away = web_sys::window()
+        .unwrap()
+        .document()
+        .unwrap()
+        .create_element("a")
+        .unwrap();
+    away.set_attribute("href", "/list").unwrap();
+    root.append_child(&away).unwrap();
+    click(&root, "a[href='/list']");
+    assert_eq!(pathname(), here);
+    wait_for("the confirm dialog", || {
+        root.query_selector("mwc-dialog[open]").unwrap().is_some()
+    })
+    .await;
+    link.send_message(editor::Msg::Leave);
+    wait_for("the post list", || pathname() == "/list").await;
+    // The entry pushed to guard the changes was taken off before going, so the list replaced it.
+    assert_eq!(history.length().unwrap(), entries + 1);
+    root.remove();
+}
+
+#[wasm_bindgen_test]
+async fn editor_goes_to_the_post_once_saved() {
+    let backend = MockBackend::install();
+    let _store = sign_in(ADMIN).await;
+    let id = create_post("Saved", "Saved body", &["rust"]).await;
+    let pathname = || web_sys::window().unwrap().location().pathname().unwrap();
+    let history = web_sys::window().unwrap().history().unwrap();
+    let entries = history.length().unwrap();
+
+    let (root, link) = mount::<editor::EditorPage>(editor::Props { id });
+    link.send_message(editor::Msg::SetCur(1));
+    wait_for_text(&root, "<p>Saved body</p>").await;
+    type_into(&root, "mwc-textfield[label='Title']", "Saved, edited");
+    link.send_message(editor::Msg::GetEditPost);
+    let post = format!("/view_post/{}", id);
+    wait_for("the post", || pathname() == post).await;
+    assert_eq!(backend.post_title(id).as_deref(), Some("Saved, edited"));
+    // The entry pushed to guard the changes was taken off before going, so the post replaced it.
+    assert_eq!(history.length().unwrap(), entries + 1);
+    root.remove();
+}
+
+#[wasm_bindgen_test]
+async fn dashboard_lists_and_deletes_drafts() {
+    let _backend = MockBackend::install();
+    let _store = sign_in(ADMIN).await;
+    let draft = |title: &str| Draft::new(title.to_string(), "Body".to_string(), String::new());
+    drafts::save(-1, &draft("Fresh idea"));
+    drafts::save(4, &draft("Stale edit"));
+
+    let (root, link) = mount::<dashboard::DashboardPage>(dashboard::Props {});
+    wait_for_text(&root, "Fresh idea").await;
+    wait_for_text(&root, "Stale edit").await;
+    assert!(root.inner_html().contains("Post 4 · saved"));
+    link.send_message(dashboard::Msg::DeleteDraft(4));
+    wait_for("the stale draft to go", || {
+        !root.inner_html().contains("Stale edit")
+    })
+    .await;
+    assert_eq!(drafts::load(4), None);
+    assert!(drafts::load(-1).is_some());
+    root.remove();
+}
+
+#[wasm_bindgen_test]
+async fn list_posts_paginates() {
+    let backend = MockBackend::install();
+    backend.seed_posts(25);
+    let _config = set_config(Config {
+        posts_per_page: 10,
+        ..Config::default()
+    });
+
+    let (root, _) = mount::<list_posts::ListPostsPage>(list_posts::Props {});
+    wait_for("the first page", || shows_title(&root, "Post 25")).await;
+    assert!(shows_title(&root, "Post 16"));
+    wait_for_text(&root, "1 min read · 4 words — Body of post 25").await;
+    assert!(!shows_title(&root, "Post 15"));
+
+    click(&root, ".button-grid > span:last-child");
+    wait_for("the second page", || shows_title(&root, "Post 15")).await;
+    assert!(!shows_title(&root, "Post 25"));
+    assert!(!shows_title(&root, "Post 5"));
+
+    click(&root, ".button-grid > span:last-child");
+    wait_for("the last page", || shows_title(&root, "Post 5")).await;
+    assert!(shows_title(&root, "Post 1"));
+    assert!(!shows_title(&root, "Post 6"));
+
+    // Next does nothing on the last page.
+    let requests = backend.calls("/api/blog/posts");
+    click(&root, ".button-grid > span:last-child");
+    click(&root, ".button-grid > span:first-child");
+    wait_for("the second page again", || shows_title(&root, "Post 15")).await;
+    // Pages already seen come from the cache.
+    assert_eq!(backend.calls("/api/blog/posts"), requests);
+    root.remove();
+}
+
+#[wasm_bindgen_test]
+async fn login_failure_shows_snackbar() {
+    let _backend = MockBackend::install();
+
+    let (root, link) = mount::<login::LoginPage>(login::Props {});
+    type_into(&root, "mwc-textfield[label='Username']", "nobody");
+    type_into(&root, "mwc-textfield[label='Password']", "secret");
+    link.send_message(login::Msg::GetLogin);
+    wait_for_text(&root, "Failed to login: Your user not exists.").await;
+
+    type_into(&root, "mwc-textfield[label='Username']", ADMIN.0);
+    link.send_message(login::Msg::GetLogin);
+    wait_for_text(&root, "Failed to login: Your password is not matched.").await;
+    root.remove();
+}
+
+#[wasm_bindgen_test]
+async fn network_errors_show_snackbar() {
+    let backend = MockBackend::install();
+    backend.set_offline(true);
+
+    let (root, link) = mount::<login::LoginPage>(login::Props {});
+    link.send_message(login::Msg::GetLogin);
+    wait_for_text(&root, "Failed to login: Network is unreachable").await;
+    root.remove();
+}
+
+#[wasm_bindgen_test]
+async fn editor_validation_shows_snackbar() {
+    let _backend = MockBackend::install();
+    let _store = sign_in(ADMIN).await;
+
+    let (root, link) = mount::<editor::EditorPage>(editor::Props { id: -1 });
+    link.send_message(editor::Msg::GetNewPost);
+    wait_for_text(&root, "Failed to create post: Too short title length.").await;
+    root.remove();
+}
+
+#[wasm_bindgen_test]
+async fn server_errors_show_on_view_post() {
+    let backend = MockBackend::install();
+    backend.seed_posts(1);
+    backend.fail_with(Some(500));
+
+    let (root, _) = mount::<view_post::ViewPost>(view_post::Props { id: 1 });
+    wait_for_text(&root, "Server responded with status 500.").await;
+    root.remove();
+}
+
+#[wasm_bindgen_test]
+async fn view_post_shows_table_of_contents() {
+    let _backend = MockBackend::install();
+    let _store = sign_in(ADMIN).await;
+    let id = create_post(
+        "Long post",
+        "# 소개\n\ntext\n\n## Details\n\ntext\n\n## Details\n\ntext",
+        &[],
+    )
+    .await;
+
+    let (root, link) = mount::<view_post::ViewPost>(view_post::Props { id });
+    wait_for("the table of contents", || {
+        root.query_selector_all(".toc li").unwrap().length() == 3
+    })
+    .await;
+    let toc = root.query_selector(".toc").unwrap().unwrap().inner_html();
+    assert!(toc.contains("href=\"#user-content-소개\""), "{}", toc);
+    assert!(toc.contains("href=\"#user-content-details-1\""), "{}", toc);
+    assert!(root
+        .query_selector("h2#user-content-details-1")
+        .unwrap()
+        .is_some());
+
+    link.send_message(view_post::Msg::ScrollTo("details".to_string()));
+    wait_for("the section to be highlighted", || {
+        root.query_selector(".toc li.active").unwrap().is_some()
+    })
+    .await;
+    let active = root.query_selector(".toc li.active").unwrap().unwrap();
+    assert_eq!(active.text_content().unwrap_or_default(), "Details");
+    assert_eq!(
+        yew::utils::window().location().hash().unwrap(),
+        "#user-content-details"
+    );
+    root.remove();
+}
+
+#[wasm_bindgen_test]
+async fn view_post_links_and_embeds_posts() {
+    let _backend = MockBackend::install();
+    let _store = sign_in(ADMIN).await;
+    let embedded = create_post("Embedded post", "The embedded body.", &[]).await;
+    let id = create_post(
+        "Linking post",
+        &format!("See [[post:{0}|that post]].\n\n![[post:{0}]]", embedded),
+        &[],
+    )
+    .await;
+
+    let (root, _link) = mount::<view_post::ViewPost>(view_post::Props { id });
+    wait_for_text(&root, "Embedded post").await;
+    let link = root
+        .query_selector(".markdown-body a.post-link")
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        link.get_attribute("href"),
+        Some(format!("/view_post/{}", embedded))
+    );
+    assert_eq!(link.text_content().unwrap_or_default(), "that post");
+    let embed = root.query_selector(".post-embed").unwrap().unwrap();
+    assert!(embed.inner_html().contains("The embedded body."));
+    root.remove();
+}
+
+#[wasm_bindgen_test]
+async fn view_post_uses_front_matter() {
+    let _backend = MockBackend::install();
+    let _store = sign_in(ADMIN).await;
+    let id = create_post("Part one", "+++\nseries = \"Measure theory\"\ncover = \"/cover.png\"\nlang = \"ko\"\ndraft = true\n+++\n본문입니다.", &[]).await;
+
+    let (root, _link) = mount::<view_post::ViewPost>(view_post::Props { id });
+    wait_for_text(&root, "Series: Measure theory").await;
+    assert!(root.query_selector(".draft-badge").unwrap().is_some());
+    let cover = root.query_selector("img.cover").unwrap().unwrap();
+    assert_eq!(cover.get_attribute("src").as_deref(), Some("/cover.png"));
+    let body = root
+        .query_selector(".markdown-body [lang='ko']")
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        body.text_content().unwrap_or_default().trim(),
+        "본문입니다."
+    );
+    root.remove();
+}
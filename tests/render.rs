@@ -0,0 +1,631 @@
+use blog_frontend::services::front_matter;
+use blog_frontend::services::highlight::Theme;
+use blog_frontend::services::render::{self, Heading, RenderCache, RenderOptions, Token};
+use std::collections::HashMap;
+use wasm_bindgen_test::*;
+
+wasm_bindgen_test_configure!(run_in_browser);
+
+fn math(tex: &str, display: bool) -> Token<'_> {
+    Token::Math { tex, display }
+}
+
+#[wasm_bindgen_test]
+fn tokenizes_all_delimiters() {
+    assert_eq!(
+        render::tokenize(r"a $x$ b $$y$$ c \(z\) d \[w\]"),
+        vec![
+            Token::Text("a "),
+            math("x", false),
+            Token::Text(" b "),
+            math("y", true),
+            Token::Text(" c "),
+            math("z", false),
+            Token::Text(" d "),
+            math("w", true),
+        ]
+    );
+    assert_eq!(
+        render::tokenize("$$\nE = mc^2\n$$"),
+        vec![math("\nE = mc^2\n", true)]
+    );
+}
+
+#[wasm_bindgen_test]
+fn dollars_that_are_not_math_stay_text() {
+    for text in &[
+        "costs $5 and $10",
+        r"costs \$5",
+        "$ spaced$",
+        "$spaced $",
+        "$a$1",
+        "$a\n\nb$",
+        "lone $",
+    ] {
+        assert_eq!(
+            render::tokenize(text),
+            vec![Token::Text(text)],
+            "{:?}",
+            text
+        );
+    }
+}
+
+#[wasm_bindgen_test]
+fn escapes_inside_math() {
+    assert_eq!(render::tokenize(r"$a\$b$"), vec![math(r"a\$b", false)]);
+    assert_eq!(
+        render::tokenize(r"\(a \\) b\)"),
+        vec![math(r"a \\) b", false)]
+    );
+}
+
+#[wasm_bindgen_test]
+fn unterminated_math_keeps_following_text() {
+    assert_eq!(
+        render::tokenize("$$x + y"),
+        vec![
+            Token::Unterminated {
+                open: "$$",
+                close: "$$"
+            },
+            Token::Text("x + y"),
+        ]
+    );
+    let html = render::render(String::from(r"see \[x")).html;
+    assert!(html.contains("class=\"math-error\""), "{}", html);
+    assert!(html.contains("x"), "{}", html);
+}
+
+#[wasm_bindgen_test]
+fn renders_display_math_and_errors() {
+    let html = render::render(String::from("$$E=mc^2$$")).html;
+    assert!(html.contains("katex-display"), "{}", html);
+    let html = render::render(String::from(r"$\frac{1}{$")).html;
+    assert!(html.contains("katex-error"), "{}", html);
+    let html = render::render(String::from(r"costs \$5")).html;
+    assert!(html.contains("costs $5"), "{}", html);
+}
+
+#[wasm_bindgen_test]
+fn code_stays_literal() {
+    let html = render::render(String::from("```sh\necho $HOME $PATH\n```")).html;
+    assert!(html.contains("echo $HOME $PATH"), "{}", html);
+    assert!(!html.contains("katex"), "{}", html);
+
+    let html = render::render(String::from("`$x$` and $y$")).html;
+    assert!(html.contains("<code>$x$</code>"), "{}", html);
+    assert!(html.contains("katex"), "{}", html);
+
+    let html = render::render(String::from("    indented $x$")).html;
+    assert!(html.contains("indented $x$"), "{}", html);
+}
+
+#[wasm_bindgen_test]
+fn markdown_does_not_see_tex() {
+    let html = render::render(String::from("$a_1 + b_1$ and $a*b*c$")).html;
+    assert!(!html.contains("<em>"), "{}", html);
+}
+
+#[wasm_bindgen_test]
+fn renders_gfm_extensions() {
+    let html = render::render(String::from("| a | b |\n|:--|--:|\n| 1 | $x$ |")).html;
+    assert!(html.contains("<th align=\"left\">a</th>"), "{}", html);
+    assert!(
+        html.contains("<td align=\"right\"><span class=\"katex\">"),
+        "{}",
+        html
+    );
+
+    let html = render::render(String::from("- [ ] todo\n- [x] done")).html;
+    assert!(
+        html.contains("<ul class=\"contains-task-list\">"),
+        "{}",
+        html
+    );
+    assert_eq!(html.matches("<li class=\"task-list-item\">").count(), 2);
+    assert!(html.contains("checked=\"\""), "{}", html);
+
+    let html = render::render(String::from("\"Quoted\" -- and...")).html;
+    assert!(
+        html.contains("\u{201C}Quoted\u{201D} \u{2013} and\u{2026}"),
+        "{}",
+        html
+    );
+}
+
+#[wasm_bindgen_test]
+fn footnotes_link_both_ways() {
+    let html = render::render(String::from("a[^n] b[^n]\n\n[^n]: Note $x$.")).html;
+    assert!(html.contains("id=\"fnref-n\""), "{}", html);
+    assert!(html.contains("id=\"fnref-n-2\""), "{}", html);
+    assert_eq!(html.matches("href=\"#fn-n\"").count(), 2);
+    assert!(
+        html.contains("<div class=\"footnote-definition\" id=\"fn-n\">"),
+        "{}",
+        html
+    );
+    assert!(
+        html.contains("class=\"footnote-backref\" href=\"#fnref-n\""),
+        "{}",
+        html
+    );
+    assert!(html.contains("katex"), "{}", html);
+}
+
+#[wasm_bindgen_test]
+fn extensions_can_be_turned_off() {
+    let options = RenderOptions {
+        tables: false,
+        footnotes: false,
+        task_lists: false,
+        smart_punctuation: false,
+        ..RenderOptions::default()
+    };
+    let html = render::render_with(
+        String::from("- [ ] \"todo\" a[^n]\n\n| a |\n|---|"),
+        options,
+    )
+    .html;
+    assert!(!html.contains("<table>"), "{}", html);
+    assert!(!html.contains("<input"), "{}", html);
+    assert!(!html.contains("footnote"), "{}", html);
+    assert!(
+        html.contains("[ ] &quot;todo&quot;") || html.contains("[ ] \"todo\""),
+        "{}",
+        html
+    );
+}
+
+#[wasm_bindgen_test]
+fn highlights_known_languages() {
+    for (lang, code, class) in &[
+        ("rust", "fn main() {}", "hl-source hl-rust"),
+        ("cpp", "int main() {}", "hl-source hl-c++"),
+        ("python", "def f(): pass", "hl-source hl-python"),
+        ("sh", "echo $HOME", "hl-source hl-shell"),
+        ("shell", "echo $HOME", "hl-source hl-shell"),
+        ("json", "{\"a\": 1}", "hl-source hl-json"),
+        ("toml", "[package]\nname = \"x\"", "hl-source hl-toml"),
+    ] {
+        let html = render::render(format!("```{}\n{}\n```", lang, code)).html;
+        assert!(
+            html.contains("<div class=\"highlight highlight-light\">"),
+            "{}",
+            html
+        );
+        assert!(html.contains(class), "{}", html);
+    }
+    let html = render::render(String::from("```toml\nname = \"x\"\n```")).html;
+    assert!(
+        html.contains("class=\"hl-entity hl-name hl-tag hl-key hl-toml\""),
+        "{}",
+        html
+    );
+}
+
+#[wasm_bindgen_test]
+fn unknown_languages_stay_plain() {
+    let html = render::render(String::from("```nope\n<b>x</b>\n```")).html;
+    assert!(
+        html.contains("<pre><code class=\"language-nope\">&lt;b&gt;x&lt;/b&gt;"),
+        "{}",
+        html
+    );
+    let html = render::render(String::from("```\nplain\n```")).html;
+    assert!(html.contains("<pre><code>plain"), "{}", html);
+}
+
+#[wasm_bindgen_test]
+fn highlight_options() {
+    let options = RenderOptions {
+        line_numbers: true,
+        code_theme: Theme::Dark,
+        ..RenderOptions::default()
+    };
+    let html = render::render_with(
+        String::from("```rust\nlet a = 1;\nlet b = 2;\n```"),
+        options,
+    )
+    .html;
+    assert!(html.contains("highlight-dark"), "{}", html);
+    assert!(
+        html.contains("<span class=\"gutter\" aria-hidden=\"true\">1\n2\n</span>"),
+        "{}",
+        html
+    );
+
+    let options = RenderOptions {
+        highlight: false,
+        ..RenderOptions::default()
+    };
+    let html = render::render_with(String::from("```rust\nlet a = 1;\n```"), options).html;
+    assert!(
+        html.contains("<pre><code class=\"language-rust\">let a = 1;"),
+        "{}",
+        html
+    );
+}
+
+#[wasm_bindgen_test]
+fn slugs_keep_hangul_and_drop_punctuation() {
+    assert_eq!(render::slugify("Hello, World!"), "hello-world");
+    assert_eq!(render::slugify("한국어 제목입니다?"), "한국어-제목입니다");
+    assert_eq!(render::slugify("  C++ & Rust_2018 "), "c--rust_2018");
+    assert_eq!(render::slugify("!!!"), "section");
+}
+
+#[wasm_bindgen_test]
+fn headings_get_unique_ids_and_a_toc() {
+    let post = render::render(String::from(
+        "# Intro\n\n## The $L^2$ `norm`\n\n# Intro\n\n## Intro-1\n\n#\n",
+    ));
+    let (html, toc) = (post.html, post.toc);
+    let heading = |level, id: &str, title: &str| Heading {
+        level,
+        id: id.to_string(),
+        title: title.to_string(),
+    };
+    assert_eq!(
+        toc,
+        vec![
+            heading(1, "user-content-intro", "Intro"),
+            heading(2, "user-content-the-l2-norm", "The L^2 norm"),
+            heading(1, "user-content-intro-1", "Intro"),
+            heading(2, "user-content-intro-1-1", "Intro-1"),
+        ]
+    );
+    assert!(
+        html.contains("<h1 id=\"user-content-intro-1\">Intro<a class=\"anchor\" href=\"#user-content-intro-1\""),
+        "{}",
+        html
+    );
+    assert!(
+        html.contains("<h2 id=\"user-content-the-l2-norm\">The <span class=\"katex\">"),
+        "{}",
+        html
+    );
+}
+
+#[wasm_bindgen_test]
+fn counts_words_in_korean_and_other_scripts() {
+    assert_eq!(render::count_words("안녕하세요, 반갑습니다."), 2);
+    assert_eq!(render::count_words("Hello, world -- again"), 3);
+    assert_eq!(render::count_words("日本語のテキスト"), 8);
+    assert_eq!(render::count_words("러스트(Rust)로 쓴 글"), 3);
+}
+
+#[wasm_bindgen_test]
+fn plain_text_leaves_out_math_and_code() {
+    let post = render::render(String::from(
+        "# 소개\n\n안녕하세요. The $L^2$ norm of `x`.\n\n```rust\nfn main() {}\n```\n\n- one\n- two",
+    ));
+    assert_eq!(post.text, "소개\n안녕하세요. The norm of .\none\ntwo");
+    assert_eq!(post.words, 7);
+    assert_eq!(post.chars, 24);
+    assert_eq!(post.reading_minutes, 1);
+}
+
+#[wasm_bindgen_test]
+fn excerpts_and_reading_time() {
+    let body = "word ".repeat(450);
+    let options = RenderOptions {
+        excerpt_words: 3,
+        ..RenderOptions::default()
+    };
+    let post = render::render_with(body, options.clone());
+    assert_eq!(post.excerpt, "word word word…");
+    assert_eq!(post.words, 450);
+    assert_eq!(post.reading_minutes, 3);
+
+    let post = render::render_with(String::from("two words"), options);
+    assert_eq!(post.excerpt, "two words");
+}
+
+#[wasm_bindgen_test]
+fn splits_front_matter() {
+    let (front_matter, body) =
+        front_matter::split("---\nmath_macros:\n  \\R: \\mathbb{R}\n---\n# Title\n");
+    assert_eq!(body, "# Title\n");
+    assert_eq!(
+        front_matter.unwrap().math_macros["\\R"],
+        "\\mathbb{R}".to_string()
+    );
+
+    let (front_matter, body) = front_matter::split("Text\n\n---\n\nmore");
+    assert_eq!(body, "Text\n\n---\n\nmore");
+    assert_eq!(front_matter, Ok(Default::default()));
+
+    let (front_matter, body) = front_matter::split("---\nmath_macros: [\n---\nText");
+    assert_eq!(body, "---\nmath_macros: [\n---\nText");
+    assert!(front_matter.unwrap_err().starts_with("Front matter: "));
+}
+
+#[wasm_bindgen_test]
+fn checks_macros() {
+    assert_eq!(render::check_macro(r"\R", r"\mathbb{R}"), Ok(()));
+    assert_eq!(
+        render::check_macro(r"\norm", r"\left\lVert #1 \right\rVert"),
+        Ok(())
+    );
+    assert_eq!(render::check_macro(r"\,", r"\{\,"), Ok(()));
+    assert!(render::check_macro("R", r"\mathbb{R}").is_err());
+    assert!(render::check_macro(r"\R2", r"\mathbb{R}^2").is_err());
+    assert!(render::check_macro(r"\R", r"\mathbb{R").is_err());
+    assert!(render::check_macro(r"\R", r"\mathbb R}").is_err());
+    assert!(render::check_macro(r"\norm", r"\lVert #x \rVert").is_err());
+}
+
+#[wasm_bindgen_test]
+fn site_and_post_macros() {
+    let mut macros = HashMap::new();
+    macros.insert(String::from(r"\R"), String::from(r"\mathbb{R}"));
+    macros.insert(String::from(r"\E"), String::from(r"\mathrm{E}"));
+    let options = RenderOptions {
+        macros,
+        ..RenderOptions::default()
+    };
+    let post = render::render_with(
+        String::from(
+            "---\nmath_macros:\n  \\E: \\mathbb{E}\n  \\bad: \\frac{1}{\n---\n$\\R$ and $\\E$",
+        ),
+        options,
+    );
+    assert!(!post.html.contains("math_macros"));
+    assert!(post.html.contains("mathbb"));
+    assert!(!post.html.contains("mathrm"));
+    assert!(!post.html.contains("katex-error"));
+    assert_eq!(
+        post.warnings,
+        vec![String::from(r"Macro \bad has a { that is never closed")]
+    );
+}
+
+#[wasm_bindgen_test]
+fn parses_yaml_and_toml_front_matter() {
+    let yaml =
+        "---\nsummary: About sets\nseries: Measure theory\nlang: ko\ndraft: true\n---\n# Title\n";
+    let toml = "+++\nsummary = \"About sets\"\nseries = \"Measure theory\"\nlang = \"ko\"\ndraft = true\n+++\n# Title\n";
+    for input in &[yaml, toml] {
+        let (front_matter, body) = front_matter::split(input);
+        let front_matter = front_matter.unwrap();
+        assert_eq!(body, "# Title\n");
+        assert_eq!(front_matter.summary.as_deref(), Some("About sets"));
+        assert_eq!(front_matter.series.as_deref(), Some("Measure theory"));
+        assert_eq!(front_matter.lang.as_deref(), Some("ko"));
+        assert!(front_matter.draft);
+    }
+}
+
+#[wasm_bindgen_test]
+fn front_matter_is_not_rendered() {
+    let post = render::render(String::from(
+        "---\nsummary: Short and sweet.\ncover: https://example.com/a.png\n---\nThe body of the post.",
+    ));
+    assert_eq!(post.html, "<p>The body of the post.</p>\n");
+    assert_eq!(post.text, "The body of the post.");
+    assert_eq!(post.excerpt, "Short and sweet.");
+    assert_eq!(
+        post.front_matter.cover_url(),
+        Some("https://example.com/a.png")
+    );
+}
+
+#[wasm_bindgen_test]
+fn leading_thematic_breaks_are_not_front_matter() {
+    let post = render::render(String::from("---\n\nSome text.\n\n---\n\nMore text."));
+    assert_eq!(
+        post.html,
+        "<hr>\n<p>Some text.</p>\n<hr>\n<p>More text.</p>\n"
+    );
+    assert!(post.warnings.is_empty(), "{:?}", post.warnings);
+}
+
+#[wasm_bindgen_test]
+fn cover_urls() {
+    let cover = |url: &str| front_matter::FrontMatter {
+        cover: Some(url.to_string()),
+        ..Default::default()
+    };
+    assert_eq!(cover("/images/a.png").cover_url(), Some("/images/a.png"));
+    assert_eq!(cover("images/a:b.png").cover_url(), Some("images/a:b.png"));
+    assert_eq!(
+        cover("HTTPS://example.com/a.png").cover_url(),
+        Some("HTTPS://example.com/a.png")
+    );
+    assert_eq!(cover("javascript:alert(1)").cover_url(), None);
+    assert_eq!(cover("data:image/png;base64,AAAA").cover_url(), None);
+    assert_eq!(cover("").cover_url(), None);
+}
+
+#[wasm_bindgen_test]
+fn links_and_embeds_posts() {
+    let post = render::render(String::from(
+        "See [[post:4]] and [[post:12|the proof]], not `[[post:5]]`.\n\n![[post:7]]\n\n```\n[[post:9]]\n```",
+    ));
+    assert!(post.html.contains(
+        "<a class=\"post-link\" href=\"/view_post/4\" rel=\"noopener noreferrer\">Post 4</a>"
+    ));
+    assert!(post
+        .html
+        .contains("href=\"/view_post/12\" rel=\"noopener noreferrer\">the proof</a>"));
+    assert!(post.html.contains("<code>[[post:5]]</code>"));
+    assert!(post
+        .html
+        .contains("<div class=\"post-embed\"><a class=\"post-link\" href=\"/view_post/7\""));
+    assert!(post.html.contains("[[post:9]]\n</code></pre>"));
+    assert_eq!(post.post_links, vec![4, 12, 7]);
+    assert_eq!(post.text, "See Post 4 and the proof, not .");
+    assert!(post.warnings.is_empty());
+}
+
+#[wasm_bindgen_test]
+fn malformed_post_links_are_reported() {
+    let post = render::render(String::from("See [[post:first]]."));
+    assert_eq!(post.html, "<p>See [[post:first]].</p>\n");
+    assert!(post.post_links.is_empty());
+    assert_eq!(
+        post.warnings,
+        vec![String::from(
+            "[[post:first]] does not name a post by its number, as in [[post:42]]"
+        )]
+    );
+}
+
+#[wasm_bindgen_test]
+fn only_generated_ids_and_app_classes_are_kept() {
+    let post = render::render(String::from(
+        "# Intro\n\n<div id=\"intro\" class=\"post-embed admonition custom\"><a class=\"post-link\" href=\"/view_post/3\">x</a></div>\n\n<span id=\"\u{9F}fn-1\" class=\"\u{9F}source-line\">y</span> <span class=\"katex\">z</span>",
+    ));
+    assert!(
+        post.html.contains("<h1 id=\"user-content-intro\">"),
+        "{}",
+        post.html
+    );
+    assert!(
+        post.html.contains("<div class=\"admonition\"><a "),
+        "{}",
+        post.html
+    );
+    assert!(!post.html.contains("post-"), "{}", post.html);
+    assert!(
+        post.html.contains("<span>y</span> <span>z</span>"),
+        "{}",
+        post.html
+    );
+    assert_eq!(post.html.matches("id=").count(), 1, "{}", post.html);
+}
+
+#[wasm_bindgen_test]
+fn renders_admonitions() {
+    let post = render::render(String::from(
+        ":::note\nA *note*.\n:::\n\n> [!WARNING]\n> Be careful.\n\n:::tip Shortcut\n```\n:::\n```\n:::",
+    ));
+    assert!(post.html.contains(
+        "<aside class=\"admonition admonition-note\" role=\"note\"><p class=\"admonition-title\">Note</p>\n<p>A <em>note</em>.</p>\n</aside>"
+    ));
+    assert!(post.html.contains(
+        "<aside class=\"admonition admonition-warning\" role=\"note\"><p class=\"admonition-title\">Warning</p>\n<p>Be careful.</p>\n</aside>"
+    ));
+    assert!(post.html.contains(
+        "<p class=\"admonition-title\">Shortcut</p>\n<pre><code>:::\n</code></pre>\n</aside>"
+    ));
+    assert_eq!(post.text, "A note.\nBe careful.");
+    assert!(post.warnings.is_empty(), "{:?}", post.warnings);
+}
+
+#[wasm_bindgen_test]
+fn numbers_and_refers_to_theorems() {
+    let post = render::render(String::from(
+        ":::theorem{#pythagoras} Pythagoras\n$a^2 + b^2 = c^2$\n:::\n\n:::theorem\nAnother.\n:::\n\n:::definition\nA set.\n:::\n\n:::proof\nBy [[ref:pythagoras]] and [[ref:theorem-2]].\n:::",
+    ));
+    assert!(post.html.contains(
+        "<section class=\"admonition admonition-theorem\" id=\"user-content-pythagoras\"><p class=\"admonition-title\">Theorem 1 (Pythagoras)</p>"
+    ));
+    assert!(post.html.contains(
+        "<section class=\"admonition admonition-theorem\" id=\"user-content-theorem-2\"><p class=\"admonition-title\">Theorem 2</p>"
+    ));
+    assert!(post.html.contains(
+        "id=\"user-content-definition-1\"><p class=\"admonition-title\">Definition 1</p>"
+    ));
+    assert!(post.html.contains(
+        "By <a class=\"cross-ref\" href=\"#user-content-pythagoras\" rel=\"noopener noreferrer\">Theorem 1</a> and <a class=\"cross-ref\" href=\"#user-content-theorem-2\" rel=\"noopener noreferrer\">Theorem 2</a>."
+    ));
+    assert!(post.warnings.is_empty(), "{:?}", post.warnings);
+}
+
+#[wasm_bindgen_test]
+fn admonition_mistakes_are_reported() {
+    let post = render::render(String::from(
+        ":::sidebar\nText.\n\n:::theorem\nUnclosed, with [[ref:missing]].",
+    ));
+    assert_eq!(
+        post.warnings,
+        vec![
+            String::from(
+                "Unknown block \"sidebar\"; blocks are note, tip, important, warning, caution, theorem, lemma, corollary, definition, example, proof"
+            ),
+            String::from("A :::theorem block is never closed by :::"),
+            String::from("[[ref:missing]] refers to no numbered block; label one with {#missing}"),
+        ]
+    );
+    assert!(post.html.ends_with("</section>\n"));
+
+    let options = RenderOptions {
+        admonitions: false,
+        ..RenderOptions::default()
+    };
+    let html = render::render_with(String::from(":::note\ntext\n:::"), options).html;
+    assert_eq!(html, "<p>:::note\ntext\n:::</p>\n");
+}
+
+#[wasm_bindgen_test]
+fn cached_renders_match_fresh_ones() {
+    let edits = [
+        "# Title\n\n$x^2$ and $$y$$.[^a]\n\n```rust\nfn main() {}\n```\n\n[^a]: Note.",
+        "# Title\n\n$x^2$ and $$y$$, then $z$.[^a]\n\n```rust\nfn main() {}\n```\n\n[^a]: Note.",
+        "---\nmath_macros:\n  \\y: \\psi\n---\n# Title\n\n$x^2$ and $$y$$, then $\\y$.[^a]\n\n```rust\nfn main() {}\n```\n\n[^a]: Note.",
+        ":::theorem\n$x^2$\n:::\n\n```rust\nfn main() { 1 }\n```",
+    ];
+    let mut cache = RenderCache::default();
+    for edit in edits.iter().chain(edits.iter().rev()) {
+        let options = RenderOptions {
+            line_numbers: edit.contains('#'),
+            ..RenderOptions::default()
+        };
+        assert_eq!(
+            cache.render_with(edit.to_string(), options.clone()),
+            render::render_with(edit.to_string(), options)
+        );
+    }
+    let again = cache.render_with(edits[3].to_string(), RenderOptions::default());
+    assert_eq!(
+        again,
+        render::render_with(edits[3].to_string(), RenderOptions::default())
+    );
+}
+
+#[wasm_bindgen_test]
+fn cache_sanitizes_only_changed_blocks() {
+    let options = RenderOptions {
+        source_lines: true,
+        ..RenderOptions::default()
+    };
+    let edits = [
+        "# Title\n\nOne.[^a]\n\nTwo.\n\n- three\n\n---\n\n[^a]: Note.",
+        "# Title\n\nAdded.\n\nOne.[^a]\n\nTwo, edited.\n\n- three\n\n---\n\n[^a]: Note.",
+        "# Title\n\nAdded.\n\nOne.[^a]\n\nTwo, edited.\n\n- three\n\n---\n\n[^a]: Note.",
+        "# Title\n\n:::note\nAdded.\n:::\n\nOne.[^a]\n\nTwo, edited.\n\n- three\n\n---\n\n[^a]: Note.",
+    ];
+    let mut cache = RenderCache::default();
+    for (edit, sanitized) in edits.iter().zip(&[6, 2, 0, 1]) {
+        assert_eq!(
+            cache.render_with(edit.to_string(), options.clone()),
+            render::render_with(edit.to_string(), options.clone())
+        );
+        assert_eq!(cache.sanitized(), *sanitized, "{}", edit);
+    }
+}
+
+#[wasm_bindgen_test]
+fn marks_source_lines_of_blocks() {
+    let input = "---\nsummary: x\n---\n# Heading\n\nMath $$\na\n$$ in text.\n\n:::note\nInside.\n:::\n\n> [!TIP]\n> Quoted.\n\n- item\n\n<div>\n\nraw\n\n</div>\n";
+    let options = RenderOptions {
+        source_lines: true,
+        ..RenderOptions::default()
+    };
+    let html = render::render_with(input.to_string(), options.clone()).html;
+    let lines: Vec<usize> = html
+        .split("data-source-line=\"")
+        .skip(1)
+        .map(|rest| rest[..rest.find('"').unwrap()].parse().unwrap())
+        .collect();
+    assert_eq!(lines, vec![3, 5, 9, 10, 13, 14, 16, 18, 20]);
+    assert!(html.starts_with("<span class=\"source-line\" data-source-line=\"3\"></span><h1"));
+
+    let html = render::render_with("Text[^a]\n\n[^a]: Note.\n".to_string(), options).html;
+    assert_eq!(html.matches("class=\"source-line\"").count(), 1);
+
+    let html = render::render_with(input.to_string(), RenderOptions::default()).html;
+    assert!(!html.contains("source-line"));
+}
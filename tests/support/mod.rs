@@ -0,0 +1,652 @@
+//! In-browser mock of the blog backend, shared by the integration tests.
+//!
+//! `MockBackend::install` replaces `window.fetch`, so every request the app sends to
+//! `/api/account_service/*` or `/api/blog/*` is answered from in-memory state. Anything else
+//! still goes to the real `fetch`. Dropping the backend puts the original back.
+
+#![allow(dead_code)]
+
+use blog_frontend::api::{cache, ApiClient, LoginForm, NewPostForm};
+use blog_frontend::config::{self, Config};
+use blog_frontend::services::drafts;
+use blog_frontend::services::store::{self, AppStore};
+use blog_frontend::services::token::{self, Backend};
+use chrono::{Duration, NaiveDateTime, Utc};
+use gloo_timers::future::TimeoutFuture;
+use js_sys::{Function, Promise, Reflect};
+use serde_json::{json, Value};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::{Rc, Weak};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::{future_to_promise, JsFuture};
+use web_sys::{Element, Event, Headers, Request, Response, ResponseInit};
+use yew::agent::{Bridge, Bridged};
+use yew::{App, Callback, Component, ComponentLink};
+
+pub const ADMIN: (&str, &str) = ("admin", "admin-password");
+pub const GUEST: (&str, &str) = ("guest", "guest-password");
+
+thread_local! {
+    /// The state of the backend installed last, for helpers not handed the backend itself.
+    static INSTALLED: RefCell<Weak<RefCell<State>>> = RefCell::new(Weak::new());
+}
+
+struct User {
+    pk: i64,
+    username: String,
+    pass: String,
+    nickname: String,
+    email: String,
+    admin: bool,
+}
+
+impl User {
+    fn to_json(&self) -> Value {
+        json!({
+            "pk": self.pk,
+            "username": self.username,
+            "nickname": self.nickname,
+            "email": self.email,
+            "level": if self.admin { "Admin" } else { "Default" },
+        })
+    }
+}
+
+struct Post {
+    id: i64,
+    title: String,
+    body: String,
+    author: i64,
+    tags: Vec<String>,
+    created_at: NaiveDateTime,
+    modified_at: NaiveDateTime,
+}
+
+impl Post {
+    fn to_public(&self) -> Value {
+        json!({
+            "title": self.title,
+            "body": self.body,
+            "author": self.author,
+            "tags": self.tags,
+            "created_at": self.created_at,
+            "modified_at": self.modified_at,
+        })
+    }
+
+    fn to_header(&self) -> Value {
+        json!({
+            "id": self.id,
+            "title": self.title,
+            "author": self.author,
+            "created_at": self.created_at,
+            "modified_at": self.modified_at,
+        })
+    }
+}
+
+#[derive(Default)]
+struct State {
+    users: Vec<User>,
+    posts: Vec<Post>,
+    next_post_id: i64,
+    /// Every token ever issued, by user pk. Any of them can be refreshed.
+    issued: HashMap<String, i64>,
+    /// Tokens currently accepted for authenticated requests.
+    sessions: HashMap<String, i64>,
+    tokens_issued: u32,
+    offline: bool,
+    fail_with: Option<u16>,
+    /// Milliseconds to hold the answers to requests to a path for, once they are made.
+    delays: HashMap<String, u32>,
+    /// `"METHOD /api/..."` for every request answered, in order.
+    calls: Vec<String>,
+}
+
+type Reply = (u16, Value);
+
+fn blog(error: &str) -> Reply {
+    (200, json!({ "status": true, "body": { "error": error } }))
+}
+
+fn query_i64(query: &HashMap<String, String>, key: &str) -> i64 {
+    query.get(key).and_then(|v| v.parse().ok()).unwrap_or(0)
+}
+
+impl State {
+    fn seeded() -> Self {
+        let mut state = Self {
+            next_post_id: 1,
+            ..Self::default()
+        };
+        for (pk, (username, pass), admin) in [(1, ADMIN, true), (2, GUEST, false)].iter() {
+            state.users.push(User {
+                pk: *pk,
+                username: username.to_string(),
+                pass: pass.to_string(),
+                nickname: format!("{} nickname", username),
+                email: format!("{}@example.com", username),
+                admin: *admin,
+            });
+        }
+        state
+    }
+
+    /// Issues a JWT-shaped token for `pk` that expires in an hour. The signature is fake.
+    fn issue(&mut self, pk: i64) -> String {
+        self.tokens_issued += 1;
+        let now = Utc::now().timestamp();
+        let claims = json!({
+            "sub": pk.to_string(),
+            "iat": now,
+            "exp": now + 60 * 60,
+            "jti": self.tokens_issued,
+        });
+        let payload = base64::encode_config(claims.to_string(), base64::URL_SAFE_NO_PAD);
+        let token = format!("eyJhbGciOiJIUzI1NiJ9.{}.mock-signature", payload);
+        self.issued.insert(token.clone(), pk);
+        self.sessions.insert(token.clone(), pk);
+        token
+    }
+
+    fn user(&self, pk: i64) -> Option<&User> {
+        self.users.iter().find(|u| u.pk == pk)
+    }
+
+    fn session(&self, token: &str) -> Option<&User> {
+        self.sessions.get(token).and_then(|pk| self.user(*pk))
+    }
+
+    fn handle(
+        &mut self,
+        method: &str,
+        path: &str,
+        query: &HashMap<String, String>,
+        body: Value,
+    ) -> Reply {
+        self.calls.push(format!("{} {}", method, path));
+        if let Some(code) = self.fail_with {
+            return (code, json!({}));
+        }
+        match (method, path) {
+            ("POST", "/api/account_service/login") => self.login(&body),
+            ("POST", "/api/account_service/register") => self.register(&body),
+            ("POST", "/api/account_service/refresh") => self.refresh(&body),
+            ("GET", "/api/account_service/info") => {
+                match self.session(query.get("token").map_or("", String::as_str)) {
+                    Some(user) => (200, json!({ "status": true, "body": user.to_json() })),
+                    None => (401, json!({})),
+                }
+            }
+            ("GET", "/api/account_service/get_user") => match self.user(query_i64(query, "pk")) {
+                Some(user) => (200, json!({ "status": true, "body": user.to_json() })),
+                None => (200, json!({ "status": false, "body": null })),
+            },
+            ("GET", "/api/blog/count_posts") => (
+                200,
+                json!({ "status": true, "body": { "error": "Nothing", "count": self.posts.len() } }),
+            ),
+            ("GET", "/api/blog/recent_posts") => {
+                let count = query_i64(query, "count") as usize;
+                let ids: Vec<i64> = self.newest_first().take(count).map(|p| p.id).collect();
+                (
+                    200,
+                    json!({ "status": true, "body": { "error": "Nothing", "posts": ids } }),
+                )
+            }
+            ("GET", "/api/blog/posts") => {
+                let start = query_i64(query, "start") as usize;
+                let count = query_i64(query, "count") as usize;
+                let posts: Vec<Value> = self
+                    .newest_first()
+                    .skip(start)
+                    .take(count)
+                    .map(Post::to_header)
+                    .collect();
+                (
+                    200,
+                    json!({ "status": true, "body": { "error": "Nothing", "posts": posts } }),
+                )
+            }
+            ("POST", "/api/blog/view_post") => self.view_post(&body),
+            ("POST", "/api/blog/new_post") => self.new_post(&body),
+            ("POST", "/api/blog/edit_post") => self.edit_post(&body),
+            ("POST", "/api/blog/delete_post") => self.delete_post(&body),
+            _ => (404, json!({})),
+        }
+    }
+
+    fn newest_first(&self) -> impl Iterator<Item = &Post> {
+        self.posts.iter().rev()
+    }
+
+    fn login(&mut self, body: &Value) -> Reply {
+        let found = self
+            .users
+            .iter()
+            .find(|u| u.username == body["username"].as_str().unwrap_or_default())
+            .map(|u| (u.pk, u.pass == body["pass"].as_str().unwrap_or_default()));
+        let (result, token) = match found {
+            None => ("UserNotExists", None),
+            Some((_, false)) => ("PassNotMatched", None),
+            Some((pk, true)) => ("Nothing", Some(self.issue(pk))),
+        };
+        (
+            200,
+            json!({ "status": true, "body": { "result": result, "token": token } }),
+        )
+    }
+
+    fn register(&mut self, body: &Value) -> Reply {
+        let field = |name: &str| body[name].as_str().unwrap_or_default().to_string();
+        let result = if self.users.iter().any(|u| u.username == field("username")) {
+            "UsernameAlreadyExists"
+        } else if self.users.iter().any(|u| u.email == field("email")) {
+            "EmailAlreadyExists"
+        } else {
+            let pk = self.users.len() as i64 + 1;
+            self.users.push(User {
+                pk,
+                username: field("username"),
+                pass: field("pass"),
+                nickname: field("nickname"),
+                email: field("email"),
+                admin: false,
+            });
+            "Nothing"
+        };
+        (200, json!({ "status": true, "body": { "result": result } }))
+    }
+
+    fn refresh(&mut self, body: &Value) -> Reply {
+        let pk = self
+            .issued
+            .get(body["token"].as_str().unwrap_or_default())
+            .copied();
+        let (result, token) = match pk {
+            Some(pk) => ("Nothing", Some(self.issue(pk))),
+            None => ("UserNotExists", None),
+        };
+        (
+            200,
+            json!({ "status": true, "body": { "result": result, "token": token } }),
+        )
+    }
+
+    /// The user behind the `AsRequest` token, `Ok(None)` without one, or the `AuthError` reply.
+    fn author(&self, body: &Value) -> Result<Option<(i64, bool)>, Reply> {
+        match body["token"].as_str().unwrap_or_default() {
+            "" => Ok(None),
+            token => match self.session(token) {
+                Some(user) => Ok(Some((user.pk, user.admin))),
+                None => Err(blog("AuthError")),
+            },
+        }
+    }
+
+    fn view_post(&self, body: &Value) -> Reply {
+        if let Err(reply) = self.author(body) {
+            return reply;
+        }
+        let id = body["body"]["id"].as_i64().unwrap_or_default();
+        let post = self.posts.iter().find(|p| p.id == id).map(Post::to_public);
+        (
+            200,
+            json!({ "status": true, "body": { "error": "Nothing", "post": post } }),
+        )
+    }
+
+    fn new_post(&mut self, body: &Value) -> Reply {
+        let author = match self.author(body) {
+            Ok(Some((pk, true))) => pk,
+            Ok(Some(_)) => return blog("PermissionError"),
+            Ok(None) => return blog("AuthError"),
+            Err(reply) => return reply,
+        };
+        let form = &body["body"];
+        let title = form["title"].as_str().unwrap_or_default();
+        let text = form["body"].as_str().unwrap_or_default();
+        if title.is_empty() {
+            return blog("TooShortTitle");
+        }
+        if text.is_empty() {
+            return blog("TooShortBody");
+        }
+        let now = Utc::now().naive_utc();
+        self.posts.push(Post {
+            id: self.next_post_id,
+            title: title.to_string(),
+            body: text.to_string(),
+            author,
+            tags: serde_json::from_value(form["tag"].clone()).unwrap_or_default(),
+            created_at: now,
+            modified_at: now,
+        });
+        self.next_post_id += 1;
+        blog("Nothing")
+    }
+
+    fn edit_post(&mut self, body: &Value) -> Reply {
+        let author = match self.author(body) {
+            Ok(Some((pk, _))) => pk,
+            Ok(None) => return blog("AuthError"),
+            Err(reply) => return reply,
+        };
+        let form = &body["body"];
+        let id = form["pk"].as_i64().unwrap_or_default();
+        let post = match self.posts.iter_mut().find(|p| p.id == id) {
+            Some(post) if post.author == author => post,
+            Some(_) => return blog("PermissionError"),
+            None => return blog("DatabaseError"),
+        };
+        post.title = form["title"].as_str().unwrap_or_default().to_string();
+        post.body = form["body"].as_str().unwrap_or_default().to_string();
+        post.tags = serde_json::from_value(form["tag"].clone()).unwrap_or_default();
+        post.modified_at = Utc::now().naive_utc();
+        blog("Nothing")
+    }
+
+    fn delete_post(&mut self, body: &Value) -> Reply {
+        let (author, admin) = match self.author(body) {
+            Ok(Some(user)) => user,
+            Ok(None) => return blog("AuthError"),
+            Err(reply) => return reply,
+        };
+        let id = body["body"]["id"].as_i64().unwrap_or_default();
+        match self.posts.iter().position(|p| p.id == id) {
+            Some(i) if admin || self.posts[i].author == author => {
+                self.posts.remove(i);
+                blog("Nothing")
+            }
+            Some(_) => blog("PermissionError"),
+            None => blog("DatabaseError"),
+        }
+    }
+}
+
+/// Splits `http://host/api/x?a=1&b=2` into `/api/x` and its query pairs.
+fn split_url(url: &str) -> Option<(String, HashMap<String, String>)> {
+    let path = &url[url.find("/api/")?..];
+    let (path, query) = match path.find('?') {
+        Some(i) => (&path[..i], &path[i + 1..]),
+        None => (path, ""),
+    };
+    let query = query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| match pair.find('=') {
+            Some(i) => (pair[..i].to_string(), pair[i + 1..].to_string()),
+            None => (pair.to_string(), String::new()),
+        })
+        .collect();
+    Some((path.to_string(), query))
+}
+
+fn respond((status, body): Reply) -> Result<JsValue, JsValue> {
+    let headers = Headers::new()?;
+    headers.set("content-type", "application/json")?;
+    let mut init = ResponseInit::new();
+    init.status(status).headers(&headers);
+    Ok(Response::new_with_opt_str_and_init(Some(&body.to_string()), &init)?.into())
+}
+
+pub struct MockBackend {
+    state: Rc<RefCell<State>>,
+    original: JsValue,
+    _fetch: Closure<dyn FnMut(JsValue, JsValue) -> Promise>,
+}
+
+impl MockBackend {
+    /// Starts a fresh backend with an `admin` and a `guest` account and no posts.
+    ///
+    /// Also resets the client side: tokens go to the in-memory store, and caches and drafts
+    /// are cleared.
+    pub fn install() -> Self {
+        token::set_backend(Backend::Memory);
+        token::clear();
+        cache::clear();
+        drafts::clear();
+
+        let window = web_sys::window().unwrap();
+        let original = Reflect::get(&window, &"fetch".into()).unwrap();
+        let state = Rc::new(RefCell::new(State::seeded()));
+        INSTALLED.with(|installed| *installed.borrow_mut() = Rc::downgrade(&state));
+
+        let handler_state = state.clone();
+        let passthrough = original.clone();
+        let fetch = Closure::wrap(Box::new(move |input: JsValue, init: JsValue| {
+            let request = match input.dyn_ref::<Request>() {
+                Some(request) => request.clone(),
+                None => {
+                    let fetch: &Function = passthrough.unchecked_ref();
+                    return fetch
+                        .call2(&JsValue::NULL, &input, &init)
+                        .map_or_else(|e| Promise::reject(&e), Promise::unchecked_from_js);
+                }
+            };
+            let (path, query) = match split_url(&request.url()) {
+                Some(parts) => parts,
+                None => {
+                    let fetch: &Function = passthrough.unchecked_ref();
+                    return fetch
+                        .call1(&JsValue::NULL, &request)
+                        .map_or_else(|e| Promise::reject(&e), Promise::unchecked_from_js);
+                }
+            };
+            let state = handler_state.clone();
+            future_to_promise(async move {
+                if state.borrow().offline {
+                    return Err(js_sys::TypeError::new("Failed to fetch").into());
+                }
+                let text = JsFuture::from(request.text()?).await?;
+                let body = text
+                    .as_string()
+                    .and_then(|text| serde_json::from_str(&text).ok())
+                    .unwrap_or(Value::Null);
+                let reply = state
+                    .borrow_mut()
+                    .handle(&request.method(), &path, &query, body);
+                let delay = state.borrow().delays.get(&path).copied();
+                if let Some(ms) = delay {
+                    TimeoutFuture::new(ms).await;
+                }
+                respond(reply)
+            })
+        }) as Box<dyn FnMut(JsValue, JsValue) -> Promise>);
+        Reflect::set(&window, &"fetch".into(), fetch.as_ref()).unwrap();
+
+        Self {
+            state,
+            original,
+            _fetch: fetch,
+        }
+    }
+
+    /// Adds `count` posts by the admin, titled `Post 1`, `Post 2`, ... in creation order.
+    pub fn seed_posts(&self, count: usize) {
+        let mut state = self.state.borrow_mut();
+        let now = Utc::now().naive_utc();
+        for _ in 0..count {
+            let id = state.next_post_id;
+            state.posts.push(Post {
+                id,
+                title: format!("Post {}", id),
+                body: format!("Body of post {}", id),
+                author: 1,
+                tags: vec![],
+                created_at: now - Duration::minutes(100 - id),
+                modified_at: now - Duration::minutes(100 - id),
+            });
+            state.next_post_id += 1;
+        }
+    }
+
+    pub fn post_ids(&self) -> Vec<i64> {
+        self.state.borrow().posts.iter().map(|p| p.id).collect()
+    }
+
+    pub fn post_title(&self, id: i64) -> Option<String> {
+        let state = self.state.borrow();
+        state
+            .posts
+            .iter()
+            .find(|p| p.id == id)
+            .map(|p| p.title.clone())
+    }
+
+    /// Issues a token for `username` without going through the login endpoint.
+    pub fn token_for(&self, username: &str) -> String {
+        let mut state = self.state.borrow_mut();
+        let pk = state
+            .users
+            .iter()
+            .find(|u| u.username == username)
+            .map(|u| u.pk)
+            .expect("unknown user");
+        state.issue(pk)
+    }
+
+    /// Stops accepting every token issued so far; they can still be refreshed.
+    pub fn expire_sessions(&self) {
+        self.state.borrow_mut().sessions.clear();
+    }
+
+    /// Forgets every token issued so far, so refreshing fails too.
+    pub fn revoke_tokens(&self) {
+        let mut state = self.state.borrow_mut();
+        state.sessions.clear();
+        state.issued.clear();
+    }
+
+    /// Makes `fetch` reject as it does when the network is down.
+    pub fn set_offline(&self, offline: bool) {
+        self.state.borrow_mut().offline = offline;
+    }
+
+    /// Answers every API request with `status` and an empty body.
+    pub fn fail_with(&self, status: Option<u16>) {
+        self.state.borrow_mut().fail_with = status;
+    }
+
+    /// Holds the answers to requests to `path` for `ms` milliseconds. They are made from the
+    /// state when the request came, so later changes do not show in them.
+    pub fn delay(&self, path: &str, ms: u32) {
+        self.state.borrow_mut().delays.insert(path.to_string(), ms);
+    }
+
+    /// How many requests were made to `path`, e.g. `/api/blog/view_post`.
+    pub fn calls(&self, path: &str) -> usize {
+        let state = self.state.borrow();
+        state
+            .calls
+            .iter()
+            .filter(|call| call.split(' ').nth(1) == Some(path))
+            .count()
+    }
+}
+
+impl Drop for MockBackend {
+    fn drop(&mut self) {
+        let window = web_sys::window().unwrap();
+        Reflect::set(&window, &"fetch".into(), &self.original).unwrap();
+    }
+}
+
+/// Logs in through the API and hands the token to `AppStore`, like `LoginPage` does.
+///
+/// Keep the returned bridge alive for as long as the store should remember the login.
+pub async fn sign_in((username, pass): (&str, &str)) -> Box<dyn Bridge<AppStore>> {
+    let form = LoginForm {
+        username: username.to_string(),
+        pass: pass.to_string(),
+    };
+    let resp = ApiClient::new().login(form).await.unwrap();
+    let token = resp.body.and_then(|body| body.token).expect("login failed");
+    let mut store = AppStore::bridge(Callback::from(|_| ()));
+    store.send(store::Request::Login {
+        token,
+        remember: false,
+    });
+    store
+}
+
+/// Creates a post through the API as whoever is signed in, and returns its id.
+pub async fn create_post(title: &str, body: &str, tags: &[&str]) -> i64 {
+    let installed = || {
+        INSTALLED
+            .with(|installed| installed.borrow().upgrade())
+            .expect("no backend installed")
+    };
+    let before: Vec<i64> = installed().borrow().posts.iter().map(|p| p.id).collect();
+    let form = NewPostForm {
+        title: title.to_string(),
+        body: body.to_string(),
+        tag: tags.iter().map(|tag| tag.to_string()).collect(),
+    };
+    ApiClient::new().new_post(form).await.unwrap();
+    let state = installed();
+    let state = state.borrow();
+    state
+        .posts
+        .iter()
+        .map(|p| p.id)
+        .find(|id| !before.contains(id))
+        .expect("the post was not created")
+}
+
+/// Puts the config back as it was when dropped.
+pub struct ConfigGuard(Rc<Config>);
+
+impl Drop for ConfigGuard {
+    fn drop(&mut self) {
+        config::set((*self.0).clone());
+    }
+}
+
+/// Sets the app's config for as long as the returned guard lives.
+pub fn set_config(new: Config) -> ConfigGuard {
+    let guard = ConfigGuard(config::get());
+    config::set(new);
+    guard
+}
+
+/// Mounts `COMP` in a fresh `<div>` appended to the document body.
+pub fn mount<COMP: Component>(props: COMP::Properties) -> (Element, ComponentLink<COMP>) {
+    let document = web_sys::window().unwrap().document().unwrap();
+    let root = document.create_element("div").unwrap();
+    document.body().unwrap().append_child(&root).unwrap();
+    let link = App::<COMP>::new().mount_with_props(root.clone(), props);
+    (root, link)
+}
+
+/// Polls `done` every 20ms until it holds, failing the test after two seconds.
+pub async fn wait_for(what: &str, mut done: impl FnMut() -> bool) {
+    for _ in 0..100 {
+        if done() {
+            return;
+        }
+        TimeoutFuture::new(20).await;
+    }
+    panic!("timed out waiting for {}", what);
+}
+
+/// Types `value` into the field matching `selector`, as its `value` followed by the `input`
+/// event typing fires. Works for Material text fields as well as plain inputs.
+pub fn type_into(root: &Element, selector: &str, value: &str) {
+    let field = root
+        .query_selector(selector)
+        .unwrap()
+        .unwrap_or_else(|| panic!("nothing matches {:?}", selector));
+    Reflect::set(&field, &"value".into(), &value.into()).unwrap();
+    field.dispatch_event(&Event::new("input").unwrap()).unwrap();
+}
+
+/// Waits until the rendered markup under `root` contains `text`.
+pub async fn wait_for_text(root: &Element, text: &str) {
+    wait_for(&format!("{:?} to render", text), || {
+        root.inner_html().contains(text)
+    })
+    .await
+}
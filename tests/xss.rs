@@ -0,0 +1,142 @@
+//! Every line of `xss/payloads.txt` is rendered as a post body and the output is checked for
+//! anything that could run script or load remote content.
+
+use blog_frontend::services::render;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_test::*;
+use web_sys::{Element, HtmlTemplateElement};
+
+wasm_bindgen_test_configure!(run_in_browser);
+
+const PAYLOADS: &str = include_str!("xss/payloads.txt");
+
+const FORBIDDEN_TAGS: &[&str] = &[
+    "script", "style", "iframe", "frame", "object", "embed", "form", "button", "textarea",
+    "select", "link", "meta", "base", "body", "video", "audio", "source", "marquee",
+];
+
+const URL_ATTRIBUTES: &[&str] = &[
+    "href",
+    "src",
+    "action",
+    "formaction",
+    "xlink:href",
+    "data",
+    "srcdoc",
+    "poster",
+];
+
+/// Parses `html` into an inert `<template>`, so nothing in it can load or run.
+fn elements(html: &str) -> Vec<Element> {
+    let document = web_sys::window().unwrap().document().unwrap();
+    let template: HtmlTemplateElement = document
+        .create_element("template")
+        .unwrap()
+        .unchecked_into();
+    template.set_inner_html(html);
+    let nodes = template.content().query_selector_all("*").unwrap();
+    (0..nodes.length())
+        .filter_map(|i| nodes.item(i))
+        .map(|node| node.unchecked_into())
+        .collect()
+}
+
+fn problems(html: &str) -> Vec<String> {
+    let mut problems = vec![];
+    for element in elements(html) {
+        let tag = element.local_name().to_ascii_lowercase();
+        if FORBIDDEN_TAGS.contains(&tag.as_str()) {
+            problems.push(format!("<{}>", tag));
+        }
+        if tag == "input"
+            && (element.get_attribute("type").as_deref() != Some("checkbox")
+                || !element.has_attribute("disabled"))
+        {
+            problems.push(String::from("<input> that is not a disabled checkbox"));
+        }
+        for name in element.get_attribute_names().iter() {
+            let name = name.as_string().unwrap().to_ascii_lowercase();
+            let value = element.get_attribute(&name).unwrap_or_default();
+            let url = value.trim().to_ascii_lowercase();
+            if name.starts_with("on") {
+                problems.push(format!("{} on <{}>", name, tag));
+            } else if URL_ATTRIBUTES.contains(&name.as_str())
+                && ["javascript:", "vbscript:", "data:"]
+                    .iter()
+                    .any(|scheme| url.starts_with(scheme))
+            {
+                problems.push(format!("{}={:?} on <{}>", name, value, tag));
+            } else if name == "style"
+                && (url.contains("url(") || url.contains("expression(") || url.contains("position"))
+            {
+                problems.push(format!("style={:?} on <{}>", value, tag));
+            }
+        }
+    }
+    problems
+}
+
+#[wasm_bindgen_test]
+fn payloads_are_neutralized() {
+    let mut failures = vec![];
+    for payload in PAYLOADS.lines().filter(|line| !line.trim().is_empty()) {
+        let html = render::render(payload.to_string()).html;
+        let found = problems(&html);
+        if !found.is_empty() {
+            failures.push(format!("{}\n  => {}\n  {:?}", payload, html, found));
+        }
+    }
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
+
+#[wasm_bindgen_test]
+fn code_blocks_are_neutralized() {
+    for payload in PAYLOADS.lines().filter(|line| !line.trim().is_empty()) {
+        for lang in &["rust", "toml", payload] {
+            let html = render::render(format!("```{}\n{}\n```", lang, payload)).html;
+            assert!(problems(&html).is_empty(), "{}", html);
+        }
+    }
+}
+
+#[wasm_bindgen_test]
+fn highlighted_code_survives() {
+    let html = render::render(String::from("```rust\nfn main() {}\n```")).html;
+    assert!(
+        html.contains("<span class=\"hl-storage hl-type hl-function hl-rust\">fn</span>"),
+        "{}",
+        html
+    );
+}
+
+#[wasm_bindgen_test]
+fn katex_markup_survives() {
+    let html = render::render(String::from(r"$x^2$ and $$\sqrt{y}$$")).html;
+    assert!(html.contains("class=\"katex\""), "{}", html);
+    assert!(html.contains("<math"), "{}", html);
+    assert!(
+        html.contains("<annotation encoding=\"application/x-tex\">"),
+        "{}",
+        html
+    );
+    assert!(html.contains("style=\"height:"), "{}", html);
+    assert!(html.contains("<svg"), "{}", html);
+    assert!(problems(&html).is_empty());
+}
+
+#[wasm_bindgen_test]
+fn safe_markup_survives() {
+    let html = render::render(String::from(
+        "[link](https://example.com) ![img](https://example.com/a.png) <b>bold</b>\n\n- [x] done",
+    ))
+    .html;
+    assert!(html.contains("href=\"https://example.com\""), "{}", html);
+    assert!(
+        html.contains("src=\"https://example.com/a.png\""),
+        "{}",
+        html
+    );
+    assert!(html.contains("<b>bold</b>"), "{}", html);
+    assert!(html.contains("type=\"checkbox\""), "{}", html);
+    assert!(problems(&html).is_empty());
+}